@@ -1,6 +1,6 @@
 use crate::{
-    command::{draft::DraftCommand, explain::ExplainCommand},
-    git_entity::{diff::Diff, GitEntity},
+    command::{draft::DraftCommand, explain::ExplainCommand, multi_explain::MultiExplainCommand},
+    git_entity::{commit::Commit, diff::Diff, GitEntity},
 };
 use indoc::{formatdoc, indoc};
 use thiserror::Error;
@@ -25,6 +25,26 @@ impl AIPrompt {
         "});
 
         let base_content = match &command.git_entity {
+            GitEntity::Commit(
+                commit @ Commit {
+                    merge_diff: Some(merge_diff),
+                    ..
+                },
+            ) => {
+                formatdoc! {"
+                    Context - Merge commit ({kind}):
+
+                    Message: {msg}
+                    Changes:
+                    ```diff
+                    {diff}
+                    ```
+                    ",
+                    kind = merge_diff.describe(),
+                    msg = commit.message,
+                    diff = commit.diff
+                }
+            }
             GitEntity::Commit(commit) => {
                 formatdoc! {"
                     Context - Commit:
@@ -49,6 +69,74 @@ impl AIPrompt {
                     "
                 }
             }
+            GitEntity::Diff(Diff::WorkingTreeAll {
+                staged,
+                unstaged,
+                untracked,
+            }) => {
+                let section = |diff: &str| {
+                    if diff.is_empty() {
+                        "(none)".to_string()
+                    } else {
+                        format!("```diff\n{diff}\n```")
+                    }
+                };
+                let mut content = formatdoc! {"
+                    Context - Changes in the working tree, in sections:
+
+                    Staged, part of the next commit:
+                    {staged}
+
+                    Not staged:
+                    {unstaged}
+                    ",
+                    staged = section(staged),
+                    unstaged = section(unstaged),
+                };
+                if let Some(untracked) = untracked {
+                    content.push_str(&formatdoc! {"
+
+                        Untracked, new files not added to git yet:
+                        {untracked}
+                        ",
+                        untracked = section(untracked),
+                    });
+                }
+                content
+            }
+            GitEntity::Stash(stash) => {
+                formatdoc! {"
+                    Context - Stashed changes, not committed:
+
+                    Message: {msg}
+                    Changes:
+                    ```diff
+                    {diff}
+                    ```
+                    ",
+                    msg = stash.message,
+                    diff = stash.diff
+                }
+            }
+            GitEntity::Tag(tag) => {
+                formatdoc! {"
+                    Context - Tag {name}, changes since {previous}:
+
+                    Tag message: {msg}
+                    Commits:
+                    {commits}
+                    Changes:
+                    ```diff
+                    {diff}
+                    ```
+                    ",
+                    name = tag.name,
+                    previous = tag.previous.as_deref().unwrap_or("the start of the history"),
+                    msg = tag.message,
+                    commits = tag.commits.join("\n"),
+                    diff = tag.diff
+                }
+            }
         };
 
         let user_prompt = match &command.query {
@@ -71,7 +159,8 @@ impl AIPrompt {
                     2. Direct impact
                     "
                 },
-                GitEntity::Diff(Diff::WorkingTree { .. }) => formatdoc! {"
+                GitEntity::Diff(Diff::WorkingTree { .. } | Diff::WorkingTreeAll { .. })
+                | GitEntity::Stash(_) => formatdoc! {"
                     {base_content}
                     
                     Provide:
@@ -87,6 +176,81 @@ impl AIPrompt {
                     2. Direct impact
                     "
                 },
+                GitEntity::Tag(_) => formatdoc! {"
+                    {base_content}
+
+                    Provide release notes covering:
+                    1. New features, fixes and breaking changes
+                    2. Direct impact for users upgrading
+                    "
+                },
+            },
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+        })
+    }
+
+    pub fn build_multi_explain_prompt(
+        command: &MultiExplainCommand,
+        explanations: &[String],
+    ) -> Result<Self, AIPromptError> {
+        if command.commits.len() != explanations.len() {
+            return Err(AIPromptError(
+                "every commit needs an explanation to build an aggregate summary".into(),
+            ));
+        }
+
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that explains how a series of Git commits relate to each other.
+            Focus on the overall story: what was built, fixed or changed across the commits and why.
+            When answering specific questions, address them directly and precisely.
+            Keep explanations brief but informative and don't ask for further explanations.
+            Use markdown for clarity.
+        "});
+
+        let mut commits: Vec<_> = command.commits.iter().zip(explanations).collect();
+        commits.sort_by_key(|(commit, _)| commit.timestamp);
+
+        let base_content = commits
+            .iter()
+            .map(|(commit, explanation)| {
+                formatdoc! {"
+                    Commit {hash} ({date}):
+                    Message: {msg}
+                    Explanation:
+                    {explanation}
+                    ",
+                    hash = commit.full_hash,
+                    date = commit.date,
+                    msg = commit.message,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let user_prompt = match &command.query {
+            Some(query) => formatdoc! {"
+                Context - Commits in chronological order:
+
+                {base_content}
+
+                Question: {query}
+
+                Provide a focused answer to the question based on the commits shown above.
+                "
+            },
+            None => formatdoc! {"
+                Context - Commits in chronological order:
+
+                {base_content}
+
+                Provide:
+                1. An overall narrative of the changes
+                2. How the commits build on or relate to each other
+                "
             },
         };
 
@@ -123,7 +287,7 @@ impl AIPrompt {
             "".to_string()
         };
 
-        let user_prompt = String::from(formatdoc! {"
+        let user_prompt = formatdoc! {"
             Generate a concise git commit message written in present tense for the following code diff with the given specifications below:
 
             The output response must be in format:
@@ -141,11 +305,92 @@ impl AIPrompt {
             ```
             ",
             commit_types = command.draft_config.commit_types,
-        });
+        };
 
         Ok(AIPrompt {
             system_prompt,
             user_prompt,
         })
     }
+
+    /// Rough token count of both parts, at about four characters per token
+    pub fn estimated_tokens(&self) -> usize {
+        (self.system_prompt.chars().count() + self.user_prompt.chars().count()).div_ceil(4)
+    }
+
+    /// Smallest useful request, used to check that a provider is reachable
+    pub fn build_ping_prompt() -> Self {
+        AIPrompt {
+            system_prompt: "You are a connectivity check.".to_string(),
+            user_prompt: "Reply with the single word OK.".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(hash: &str, date: &str, timestamp: i64) -> Commit {
+        Commit {
+            full_hash: hash.to_string(),
+            message: format!("Commit {hash}"),
+            diff: String::new(),
+            author_name: "Test".to_string(),
+            author_email: "test@example.com".to_string(),
+            date: date.to_string(),
+            timestamp,
+            parents: Vec::new(),
+            merge_diff: None,
+        }
+    }
+
+    fn multi_explain(query: Option<&str>) -> MultiExplainCommand {
+        // `bbb` reads earlier but was committed five hours later, from another time zone
+        MultiExplainCommand {
+            commits: vec![
+                commit("bbb", "2024-01-01 09:30:00", 1_704_119_400),
+                commit("aaa", "2024-01-01 10:00:00", 1_704_099_600),
+            ],
+            query: query.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_multi_explain_orders_commits_by_time() {
+        let explanations = ["explains bbb".to_string(), "explains aaa".to_string()];
+        let prompt =
+            AIPrompt::build_multi_explain_prompt(&multi_explain(None), &explanations).unwrap();
+
+        let aaa = prompt.user_prompt.find("Commit aaa (").unwrap();
+        let bbb = prompt.user_prompt.find("Commit bbb (").unwrap();
+        assert!(aaa < bbb);
+        assert!(prompt.user_prompt[aaa..bbb].contains("explains aaa"));
+        assert!(prompt.user_prompt[bbb..].contains("explains bbb"));
+    }
+
+    #[test]
+    fn test_multi_explain_needs_every_explanation() {
+        let explanations = ["explains bbb".to_string()];
+        assert!(AIPrompt::build_multi_explain_prompt(&multi_explain(None), &explanations).is_err());
+    }
+
+    #[test]
+    fn test_multi_explain_query_or_narrative() {
+        let explanations = ["explains bbb".to_string(), "explains aaa".to_string()];
+
+        let narrative =
+            AIPrompt::build_multi_explain_prompt(&multi_explain(None), &explanations).unwrap();
+        assert!(narrative
+            .user_prompt
+            .contains("An overall narrative of the changes"));
+        assert!(!narrative.user_prompt.contains("Question:"));
+
+        let question = multi_explain(Some("Why was the cache removed?"));
+        let answer = AIPrompt::build_multi_explain_prompt(&question, &explanations).unwrap();
+        assert!(answer
+            .user_prompt
+            .contains("Question: Why was the cache removed?"));
+        assert!(!answer.user_prompt.contains("overall narrative"));
+    }
 }
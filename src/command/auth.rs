@@ -0,0 +1,42 @@
+use std::io::{self, BufRead, IsTerminal};
+
+use crate::{
+    config::{
+        cli::ProviderType,
+        secret::{self, Secret},
+    },
+    error::LumenError,
+};
+
+/// Reads the key without echoing it when run interactively, otherwise from the first line of stdin
+pub fn login(provider: ProviderType) -> Result<(), LumenError> {
+    let name = provider.to_string();
+
+    let key = if io::stdin().is_terminal() {
+        rpassword::prompt_password(format!("API key for {name}: "))?
+    } else {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line)?;
+        line
+    };
+
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(LumenError::InvalidArguments("no API key given".into()));
+    }
+
+    secret::keyring_set(&name, &Secret::new(key))?;
+    eprintln!("Stored the {name} API key in the system keyring");
+    Ok(())
+}
+
+pub fn logout(provider: ProviderType) -> Result<(), LumenError> {
+    let name = provider.to_string();
+
+    if secret::keyring_delete(&name)? {
+        eprintln!("Removed the {name} API key from the system keyring");
+    } else {
+        eprintln!("No {name} API key was stored");
+    }
+    Ok(())
+}
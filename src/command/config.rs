@@ -0,0 +1,447 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use spinoff::{spinners, Color, Spinner, Streams};
+
+use crate::{
+    config::{
+        cli::{OutputFormat, ProviderType},
+        configuration::ConfigOrigin,
+        discovery,
+        secret::{self, Secret},
+        LumenConfig,
+    },
+    error::LumenError,
+    git_entity::repo::Repo,
+    output::Printer,
+    provider::{GenerationParams, LumenProvider},
+    Cli,
+};
+
+#[derive(Serialize)]
+struct ConfigEntry<'a> {
+    key: &'static str,
+    value: Option<String>,
+    origin: &'a ConfigOrigin,
+}
+
+#[derive(Serialize)]
+struct ConfigDocument<'a> {
+    config: Vec<ConfigEntry<'a>>,
+}
+
+/// Prints the effective configuration, one `key = value` per line
+pub fn show(config: &LumenConfig, origin: bool, printer: &Printer) -> Result<(), LumenError> {
+    let entries: Vec<ConfigEntry> = LumenConfig::KEYS
+        .iter()
+        .map(|&key| ConfigEntry {
+            key,
+            value: config.display_value(key),
+            origin: config.origin(key),
+        })
+        .collect();
+
+    if printer.format() == OutputFormat::Json {
+        return printer.json_document(&ConfigDocument { config: entries });
+    }
+
+    let width = LumenConfig::KEYS
+        .iter()
+        .map(|key| key.len())
+        .max()
+        .unwrap_or(0);
+    for entry in entries {
+        let value = entry.value.unwrap_or_else(|| "(unset)".to_string());
+        if origin {
+            println!("{:width$} = {value}  # {}", entry.key, entry.origin);
+        } else {
+            println!("{:width$} = {value}", entry.key);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Serialize)]
+struct Issue {
+    severity: Severity,
+    message: String,
+}
+
+impl Issue {
+    /// Loading errors already name the file and position
+    fn error(e: LumenError) -> Self {
+        Issue {
+            severity: Severity::Error,
+            message: e.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ValidationDocument {
+    valid: bool,
+    files: Vec<PathBuf>,
+    issues: Vec<Issue>,
+}
+
+/// Checks each config file on its own, then the effective configuration they add up to
+pub fn validate(cli: &Cli, path: Option<&str>, printer: &Printer) -> Result<(), LumenError> {
+    let mut layers = LumenConfig::files(cli);
+    let checked: Vec<PathBuf> = match path {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            layers.retain(|(layer, _)| *layer != path);
+            layers.push((path.clone(), ConfigOrigin::File(path.clone())));
+            vec![path]
+        }
+        None => layers.iter().map(|(path, _)| path.clone()).collect(),
+    };
+
+    let mut issues = Vec::new();
+    let mut config = LumenConfig::default();
+    issues.extend(config.merge_files(layers).into_iter().map(Issue::error));
+    if issues.is_empty() {
+        match config.resolve(cli) {
+            Ok(()) => issues.extend(check_consistency(&config)),
+            Err(e) => issues.push(Issue::error(e)),
+        }
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+
+    if printer.format() == OutputFormat::Json {
+        return printer.json_document(&ValidationDocument {
+            valid: errors == 0,
+            files: checked,
+            issues,
+        });
+    }
+
+    for issue in &issues {
+        let severity = match issue.severity {
+            Severity::Error => printer.paint("error", 91),
+            Severity::Warning => printer.paint("warning", 93),
+        };
+        println!("{severity}: {}", issue.message);
+    }
+
+    if errors > 0 {
+        return Err(LumenError::InvalidConfiguration(format!(
+            "{errors} error(s) found"
+        )));
+    }
+    println!("{} config file(s) checked, no errors found", checked.len());
+    Ok(())
+}
+
+fn check_consistency(config: &LumenConfig) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut issue = |severity, message| issues.push(Issue { severity, message });
+
+    let provider = config.provider;
+    if provider.requires_api_key() && config.api_key.is_none() {
+        issue(
+            Severity::Error,
+            format!(
+                "`{provider}` needs an API key: set `api_key`, `api_key_cmd` or LUMEN_API_KEY, or run `lumen auth login {provider}`"
+            ),
+        );
+    }
+    if !provider.requires_api_key() && config.api_key.is_some() {
+        issue(
+            Severity::Warning,
+            format!("`api_key` is set but `{provider}` does not use one"),
+        );
+    }
+    if provider.requires_model() && config.model.is_none() {
+        issue(Severity::Error, format!("`{provider}` needs a `model`"));
+    }
+    if config.api_base_url.is_some() && provider == ProviderType::Mock {
+        issue(
+            Severity::Warning,
+            format!("`api_base_url` is set but `{provider}` does not use it"),
+        );
+    }
+
+    if provider == ProviderType::Phind && config.generation != GenerationParams::default() {
+        issue(
+            Severity::Warning,
+            "`phind` ignores the `generation` parameters".to_string(),
+        );
+    }
+
+    for (name, profile) in &config.profiles {
+        let Some(provider) = profile.provider else {
+            continue;
+        };
+        let has_key = profile.api_key.is_some()
+            || profile.api_key_env.is_some()
+            || profile.api_key_cmd.is_some();
+        if provider.requires_api_key() && !has_key && config.api_key.is_none() {
+            issue(
+                Severity::Warning,
+                format!("profile `{name}` uses `{provider}` but does not say where its API key comes from"),
+            );
+        }
+    }
+
+    issues
+}
+
+/// Settings written by `lumen config init`
+#[derive(Serialize)]
+struct InitFile {
+    provider: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_base_url: Option<String>,
+}
+
+/// Prints `question` on stderr and returns the trimmed answer, or `default` if it is empty
+fn ask(question: &str, default: &str) -> Result<String, LumenError> {
+    if default.is_empty() {
+        eprint!("{question}: ");
+    } else {
+        eprint!("{question} [{default}]: ");
+    }
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(LumenError::InvalidArguments(
+            "`config init` was interrupted".into(),
+        ));
+    }
+
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+fn confirm(question: &str, default: bool) -> Result<bool, LumenError> {
+    let answer = ask(question, if default { "Y/n" } else { "y/N" })?;
+    Ok(match answer.to_lowercase().as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    })
+}
+
+/// Writes the global config, or with `local` a `.lumen.toml` in the repository containing `dir`
+pub async fn init(local: bool, dir: Option<&Path>) -> Result<(), LumenError> {
+    let (dir, names, name) = if local {
+        let root = Repo::discover(dir)?.root().to_path_buf();
+        (root, discovery::REPO_CONFIG_FILES, ".lumen.toml")
+    } else {
+        let dir = discovery::global_config_dir().ok_or_else(|| {
+            LumenError::InvalidConfiguration("could not find the home directory".into())
+        })?;
+        (dir, discovery::GLOBAL_CONFIG_FILES, "config.toml")
+    };
+    // Only one file is read, a new one would hide or be hidden by the existing one
+    if let Some(existing) = other_config_file(&dir, names, name) {
+        return Err(LumenError::InvalidConfiguration(format!(
+            "{} already holds the configuration, edit it instead",
+            existing.display()
+        )));
+    }
+    let path = dir.join(name);
+
+    if path.exists()
+        && !confirm(
+            &format!("{} already exists, overwrite it?", path.display()),
+            false,
+        )?
+    {
+        return Ok(());
+    }
+
+    let providers: Vec<String> = ProviderType::value_variants()
+        .iter()
+        .filter(|provider| **provider != ProviderType::Mock)
+        .map(ToString::to_string)
+        .collect();
+    let provider = loop {
+        let answer = ask(
+            &format!("Provider ({})", providers.join(", ")),
+            &ProviderType::Phind.to_string(),
+        )?;
+        match answer.parse::<ProviderType>() {
+            Ok(provider) => break provider,
+            Err(e) => eprintln!("{e}"),
+        }
+    };
+
+    let model = loop {
+        let answer = if provider.requires_model() {
+            ask("Model", "")?
+        } else {
+            ask("Model (empty for the provider default)", "")?
+        };
+        if !answer.is_empty() {
+            break Some(answer);
+        }
+        if !provider.requires_model() {
+            break None;
+        }
+        eprintln!("`{provider}` needs a model, eg: llama3");
+    };
+
+    let api_base_url = match provider {
+        ProviderType::Openai => Some(ask("API base URL (empty for api.openai.com)", "")?),
+        ProviderType::Ollama => Some(ask(
+            "Ollama API URL (empty for http://localhost:11434/api/generate)",
+            "",
+        )?),
+        _ => None,
+    }
+    .filter(|url| !url.is_empty());
+    if local && api_base_url.is_some() {
+        eprintln!(
+            "note: the API base URL in {} is only used once the repository is listed in `trusted_repositories` of your global config",
+            path.display()
+        );
+    }
+
+    let api_key = if provider.requires_api_key() {
+        let key = rpassword::prompt_password(format!("API key for {provider}: "))?;
+        Some(Secret::new(key.trim())).filter(|key| !key.expose().is_empty())
+    } else {
+        None
+    };
+
+    let client = reqwest::Client::new();
+    let lumen_provider = LumenProvider::new(
+        client,
+        provider,
+        api_key.clone(),
+        model.clone(),
+        api_base_url.clone(),
+        GenerationParams::default(),
+    )?;
+
+    let mut spinner = Spinner::new_with_stream(
+        spinners::Dots,
+        format!("Testing {provider}..."),
+        Color::Blue,
+        Streams::Stderr,
+    );
+    match lumen_provider.ping().await {
+        Ok(_) => spinner.success(&format!(
+            "{provider} answered using {}",
+            lumen_provider.model()
+        )),
+        Err(e) => {
+            spinner.fail(&format!("{provider} did not answer: {e}"));
+            if !confirm("Write the configuration anyway?", false)? {
+                return Ok(());
+            }
+        }
+    }
+
+    let mut file_key = None;
+    if let Some(key) = api_key {
+        if confirm(
+            "Store the API key in the system keyring instead of the file?",
+            true,
+        )? {
+            secret::keyring_set(&provider.to_string(), &key)?;
+            eprintln!("Stored the {provider} API key in the system keyring");
+        } else {
+            if local {
+                eprintln!(
+                    "warning: {} may end up committed with the key in it",
+                    path.display()
+                );
+            }
+            file_key = Some(key.expose().to_string());
+        }
+    }
+
+    let content = toml::to_string(&InitFile {
+        provider: provider.to_string(),
+        model,
+        api_key: file_key,
+        api_base_url,
+    })
+    .map_err(|e| LumenError::InvalidConfiguration(e.to_string()))?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_private(&path, &content)?;
+    eprintln!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// A config file in `dir` named after one of `names` other than `name`
+fn other_config_file(dir: &Path, names: &[&str], name: &str) -> Option<PathBuf> {
+    names
+        .iter()
+        .filter(|other| **other != name)
+        .map(|other| dir.join(other))
+        .find(|path| path.is_file())
+}
+
+/// Writes `content` readable by the current user only, it may hold an API key
+fn write_private(path: &Path, content: &str) -> Result<(), LumenError> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_file_is_private_and_alone() {
+        let dir = std::env::temp_dir().join(format!("lumen-init-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let names = discovery::GLOBAL_CONFIG_FILES;
+        assert_eq!(other_config_file(&dir, names, "config.toml"), None);
+
+        write_private(&dir.join("config.toml"), "api_key = \"sk\"\n").unwrap();
+        assert_eq!(other_config_file(&dir, names, "config.toml"), None);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join("config.toml"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        fs::write(dir.join("config.json"), "{}").unwrap();
+        let other = other_config_file(&dir, names, "config.toml");
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(other, Some(dir.join("config.json")));
+    }
+}
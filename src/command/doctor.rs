@@ -0,0 +1,393 @@
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::{
+    config::{
+        cli::{OutputFormat, ProviderType},
+        configuration::PickerKind,
+        LumenConfig,
+    },
+    error::LumenError,
+    git_entity::repo::Repo,
+    output::{pager, Printer},
+    provider::{GenerationParams, LumenProvider, ModelInfo, ProviderError},
+    Cli,
+};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+#[derive(Serialize)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct DoctorDocument {
+    ok: bool,
+    checks: Vec<Check>,
+}
+
+/// Runs `program args` and returns its trimmed stdout if it succeeded
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `dir` is the directory given with `-C`, the working directory otherwise
+fn check_git(dir: Option<&Path>) -> Vec<Check> {
+    let Some(version) = run("git", &["--version"]) else {
+        return vec![Check::new("git", Status::Fail, "git was not found")
+            .hint("install git and make sure it is on your PATH")];
+    };
+    let mut checks = vec![Check::new("git", Status::Pass, version)];
+
+    let Ok(repo) = Repo::discover(dir) else {
+        checks.push(
+            Check::new("repository", Status::Warn, "not inside a git repository")
+                .hint("run lumen from a repository, or point at one with -C <dir>, to explain commits or draft messages"),
+        );
+        return checks;
+    };
+    let git = |args: &[&str]| {
+        let output = repo.git(args).ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    let branch = git(&["rev-parse", "--abbrev-ref", "HEAD"])
+        .filter(|branch| branch != "HEAD")
+        .unwrap_or_else(|| "detached HEAD".to_string());
+    let staged = git(&["diff", "--cached", "--name-only"]).map_or(0, |files| files.lines().count());
+    checks.push(Check::new(
+        "repository",
+        Status::Pass,
+        format!(
+            "{} ({branch}, {staged} staged file(s))",
+            repo.root().display()
+        ),
+    ));
+    checks
+}
+
+fn check_tools(config: Option<&LumenConfig>) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let fzf_required = config.is_some_and(|config| config.list.picker == PickerKind::Fzf);
+    checks.push(match run("fzf", &["--version"]) {
+        Some(version) => Check::new("fzf", Status::Pass, version),
+        None if fzf_required => {
+            Check::new("fzf", Status::Fail, "not found, but `list.picker` is fzf")
+                .hint("install fzf, or set \"picker\": \"builtin\"")
+        }
+        None => Check::new("fzf", Status::Skip, "not found, the builtin picker is used"),
+    });
+
+    let pager = pager::pager_command();
+    let program = pager.split_whitespace().next().unwrap_or_default();
+    checks.push(match run("sh", &["-c", &format!("command -v {program}")]) {
+        Some(path) => Check::new("pager", Status::Pass, format!("{pager} ({path})")),
+        None => Check::new("pager", Status::Warn, format!("`{pager}` was not found"))
+            .hint("install less, or set PAGER to an installed pager"),
+    });
+
+    checks
+}
+
+fn check_config(cli: &Cli) -> (Check, Option<LumenConfig>) {
+    match LumenConfig::build(cli) {
+        Ok(config) => {
+            let files: Vec<String> = LumenConfig::files(cli)
+                .into_iter()
+                .map(|(path, _)| path.display().to_string())
+                .collect();
+            let detail = if files.is_empty() {
+                "no config file, using defaults, env and flags".to_string()
+            } else {
+                files.join(", ")
+            };
+            (Check::new("config", Status::Pass, detail), Some(config))
+        }
+        Err(e) => (
+            Check::new("config", Status::Fail, e.to_string())
+                .hint("run `lumen config validate` for details"),
+            None,
+        ),
+    }
+}
+
+async fn check_provider(config: LumenConfig) -> Vec<Check> {
+    let provider_type = config.provider;
+    let origin = config.origin("provider").clone();
+    let mut checks = vec![Check::new(
+        "provider",
+        Status::Pass,
+        format!("{provider_type} (from {origin})"),
+    )];
+
+    let api_key = match config.api_key.as_ref().map(|key| key.resolve()).transpose() {
+        Ok(api_key) => api_key,
+        Err(e) => {
+            checks.push(
+                Check::new("api key", Status::Fail, e.to_string())
+                    .hint("check `api_key_cmd`, it must print the key and exit successfully"),
+            );
+            return checks;
+        }
+    };
+
+    let client = reqwest::Client::new();
+    let model_configured = config.model.is_some();
+    let key_origin = config.origin("api_key").clone();
+    let (provider, can_complete) = match LumenProvider::new(
+        client.clone(),
+        provider_type,
+        api_key.clone(),
+        config.model,
+        config.api_base_url.clone(),
+        GenerationParams::default(),
+    ) {
+        Ok(provider) => (provider, true),
+        Err(LumenError::MissingModel(_)) => {
+            checks.push(
+                Check::new(
+                    "model",
+                    Status::Fail,
+                    format!("`{provider_type}` needs a model"),
+                )
+                .hint("set `model` or LUMEN_AI_MODEL, `lumen models` lists the installed ones"),
+            );
+            match LumenProvider::for_listing(client, provider_type, api_key, config.api_base_url) {
+                Ok(provider) => (provider, false),
+                Err(_) => return checks,
+            }
+        }
+        Err(e) => {
+            checks.push(Check::new("api key", Status::Fail, e.to_string()).hint(format!(
+                "set LUMEN_API_KEY, `api_key` or `api_key_cmd`, or run `lumen auth login {provider_type}`"
+            )));
+            return checks;
+        }
+    };
+
+    let requires_key = provider_type.requires_api_key();
+    if provider_type == ProviderType::Mock {
+        checks.push(Check::new(
+            "reachability",
+            Status::Skip,
+            "offline provider, nothing is sent",
+        ));
+        checks.push(Check::new("api key", Status::Skip, "not needed"));
+    } else if !can_complete {
+        checks.push(Check::new(
+            "reachability",
+            Status::Skip,
+            "not checked without a model to send a request with",
+        ));
+        return checks;
+    } else {
+        // A real completion, listing models may need no key or send nothing at all
+        let started = Instant::now();
+        let ping = provider.ping().await;
+        let latency = started.elapsed().as_millis();
+
+        match &ping {
+            Err(ProviderError::RequestError(e)) if e.status().is_none() => {
+                checks.push(
+                    Check::new("reachability", Status::Fail, e.to_string())
+                        .hint("check your network, proxy and `api_base_url`"),
+                );
+                return checks;
+            }
+            _ => checks.push(Check::new(
+                "reachability",
+                Status::Pass,
+                format!("answered in {latency} ms"),
+            )),
+        }
+
+        match &ping {
+            Err(ProviderError::APIError(status, _))
+                if status.as_u16() == 401 || status.as_u16() == 403 =>
+            {
+                checks.push(
+                    Check::new("api key", Status::Fail, format!("rejected with {status}"))
+                        .hint(format!(
+                        "check the key, or store a new one with `lumen auth login {provider_type}`"
+                    )),
+                );
+                return checks;
+            }
+            Err(e) => {
+                checks.push(Check::new("completion", Status::Warn, e.to_string()));
+                let detail = if requires_key {
+                    "not checked, the request failed"
+                } else {
+                    "not needed"
+                };
+                checks.push(Check::new("api key", Status::Skip, detail));
+            }
+            Ok(_) if requires_key => checks.push(Check::new(
+                "api key",
+                Status::Pass,
+                format!("accepted (from {key_origin})"),
+            )),
+            Ok(_) => checks.push(Check::new("api key", Status::Skip, "not needed")),
+        }
+    }
+
+    match provider.list_models().await {
+        Ok(models) => checks.push(check_model(&provider, &models, model_configured)),
+        Err(e) => checks.push(Check::new("models", Status::Warn, e.to_string())),
+    }
+    checks
+}
+
+fn check_model(provider: &LumenProvider, models: &[ModelInfo], configured: bool) -> Check {
+    let model = provider.model();
+    let source = if configured { "configured" } else { "default" };
+
+    if model.is_empty() {
+        // Already reported when building the provider
+        return Check::new("model", Status::Skip, "no model set");
+    }
+    if provider.name() == ProviderType::Phind.to_string() {
+        return Check::new(
+            "model",
+            Status::Skip,
+            format!("{model} ({source}), phind cannot list models"),
+        );
+    }
+    if models.iter().any(|available| available.id == model) {
+        Check::new("model", Status::Pass, format!("{model} ({source})"))
+    } else {
+        Check::new(
+            "model",
+            Status::Fail,
+            format!("{model} ({source}) is not offered by {}", provider.name()),
+        )
+        .hint("pick one from `lumen models` and set `model` or LUMEN_AI_MODEL")
+    }
+}
+
+/// Checks the environment, configuration and provider, printing a report with hints
+pub async fn doctor(cli: &Cli, printer: &Printer) -> Result<(), LumenError> {
+    let mut checks = check_git(cli.repo.as_deref());
+    let (config_check, config) = check_config(cli);
+    checks.extend(check_tools(config.as_ref()));
+    checks.push(config_check);
+    if let Some(config) = config {
+        checks.extend(check_provider(config).await);
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count();
+
+    if printer.format() == OutputFormat::Json {
+        return printer.json_document(&DoctorDocument {
+            ok: failed == 0,
+            checks,
+        });
+    }
+
+    let width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+    for check in &checks {
+        let marker = match check.status {
+            Status::Pass => printer.paint("✔", 92),
+            Status::Warn => printer.paint("!", 93),
+            Status::Fail => printer.paint("✘", 91),
+            Status::Skip => printer.paint("-", 90),
+        };
+        println!("{marker} {:width$}  {}", check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("  {:width$}  hint: {hint}", "");
+        }
+    }
+
+    if failed > 0 {
+        return Err(LumenError::CommandError(format!(
+            "{failed} check(s) failed"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn statuses(provider: ProviderType) -> Vec<(&'static str, Status)> {
+        let mut config = LumenConfig::default();
+        config.provider = provider;
+        check_provider(config)
+            .await
+            .into_iter()
+            .map(|check| (check.name, check.status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_offline_provider_skips_network_checks() {
+        assert_eq!(
+            statuses(ProviderType::Mock).await,
+            [
+                ("provider", Status::Pass),
+                ("reachability", Status::Skip),
+                ("api key", Status::Skip),
+                ("model", Status::Pass),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_model_skips_reachability() {
+        assert_eq!(
+            statuses(ProviderType::Ollama).await,
+            [
+                ("provider", Status::Pass),
+                ("model", Status::Fail),
+                ("reachability", Status::Skip),
+            ]
+        );
+    }
+}
@@ -1,13 +1,17 @@
-use std::io::Write;
+use std::time::Instant;
 
 use async_trait::async_trait;
 
 use crate::{
-    config::configuration::DraftConfig, error::LumenError, git_entity::GitEntity,
-    provider::LumenProvider,
+    ai_prompt::AIPrompt,
+    config::configuration::DraftConfig,
+    error::LumenError,
+    git_entity::GitEntity,
+    output::{GenerationReport, PromptKind, PromptPreview},
+    provider::{LumenProvider, ProviderError},
 };
 
-use super::Command;
+use super::{Command, CommandOutput};
 
 pub struct DraftCommand {
     pub git_entity: GitEntity,
@@ -15,13 +19,34 @@ pub struct DraftCommand {
     pub draft_config: DraftConfig,
 }
 
+impl DraftCommand {
+    pub async fn generate(&self, provider: &LumenProvider) -> Result<GenerationReport, LumenError> {
+        let started = Instant::now();
+        let completion = provider.draft(self).await?;
+
+        Ok(GenerationReport::new(
+            PromptKind::Draft,
+            Some(&self.git_entity),
+            self.context.clone(),
+            completion,
+            started.elapsed(),
+        ))
+    }
+}
+
 #[async_trait]
 impl Command for DraftCommand {
-    async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
-        let result = provider.draft(self).await?;
+    async fn execute(&self, provider: &LumenProvider) -> Result<CommandOutput, LumenError> {
+        Ok(CommandOutput::Draft(self.generate(provider).await?))
+    }
 
-        print!("{result}");
-        std::io::stdout().flush()?;
-        Ok(())
+    fn preview(&self, provider: &LumenProvider) -> Result<Vec<PromptPreview>, LumenError> {
+        Ok(vec![PromptPreview::new(
+            PromptKind::Draft,
+            Some(&self.git_entity),
+            self.context.clone(),
+            AIPrompt::build_draft_prompt(self).map_err(ProviderError::from)?,
+            provider,
+        )])
     }
 }
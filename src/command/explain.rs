@@ -1,33 +1,64 @@
+use std::time::Instant;
+
 use async_trait::async_trait;
-use spinoff::{spinners, Color, Spinner};
 
-use crate::{error::LumenError, git_entity::GitEntity, provider::LumenProvider};
+use crate::{
+    ai_prompt::AIPrompt,
+    error::LumenError,
+    git_entity::GitEntity,
+    output::{GenerationReport, PromptKind, PromptPreview},
+    provider::{LumenProvider, ProviderError},
+};
 
-use super::{Command, LumenCommand};
+use super::{Command, CommandOutput};
 
 pub struct ExplainCommand {
     pub git_entity: GitEntity,
     pub query: Option<String>,
 }
 
+impl ExplainCommand {
+    pub fn prompt_preview(&self, provider: &LumenProvider) -> Result<PromptPreview, LumenError> {
+        Ok(PromptPreview::new(
+            PromptKind::Explain,
+            Some(&self.git_entity),
+            self.query.clone(),
+            AIPrompt::build_explain_prompt(self).map_err(ProviderError::from)?,
+            provider,
+        ))
+    }
+
+    pub async fn generate(&self, provider: &LumenProvider) -> Result<GenerationReport, LumenError> {
+        let started = Instant::now();
+        let completion = provider.explain(self).await?;
+
+        Ok(GenerationReport::new(
+            PromptKind::Explain,
+            Some(&self.git_entity),
+            self.query.clone(),
+            completion,
+            started.elapsed(),
+        ))
+    }
+}
+
 #[async_trait]
 impl Command for ExplainCommand {
-    async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
-        LumenCommand::print_with_mdcat(self.git_entity.format_static_details())?;
-        if let Some(query) = &self.query {
-            LumenCommand::print_with_mdcat(format!("`query`: {query}"))?;
-        }
+    async fn execute(&self, provider: &LumenProvider) -> Result<CommandOutput, LumenError> {
+        Ok(CommandOutput::Explanation {
+            git_entity: self.git_entity.clone(),
+            report: self.generate(provider).await?,
+        })
+    }
+
+    fn preview(&self, provider: &LumenProvider) -> Result<Vec<PromptPreview>, LumenError> {
+        Ok(vec![self.prompt_preview(provider)?])
+    }
 
-        let spinner_text = match &self.query {
+    fn progress(&self, _provider: &LumenProvider) -> Option<String> {
+        Some(match self.query {
             Some(_) => "Generating answer...".to_string(),
             None => "Generating summary...".to_string(),
-        };
-
-        let mut spinner = Spinner::new(spinners::Dots, spinner_text, Color::Blue);
-        let result = provider.explain(self).await?;
-        spinner.success("Done");
-
-        LumenCommand::print_with_mdcat(result)?;
-        Ok(())
+        })
     }
 }
@@ -1,25 +1,46 @@
-use async_trait::async_trait;
-
 use crate::{
+    config::{
+        cli::LogFilter,
+        configuration::{ListConfig, PickerKind},
+    },
     error::LumenError,
-    git_entity::{commit::Commit, GitEntity},
-    provider::LumenProvider,
+    git_entity::{commit::Commit, repo::Repo, GitEntity},
+    picker::{self, PickerEntry},
 };
 
-use super::{explain::ExplainCommand, Command, LumenCommand};
+use super::{explain::ExplainCommand, multi_explain::MultiExplainCommand, Command, LumenCommand};
+
+pub struct ListCommand {
+    pub config: ListConfig,
+    pub filter: LogFilter,
+    pub repo: Repo,
+}
+
+impl ListCommand {
+    /// Lets the user pick commits and returns the command explaining them, if any were picked
+    pub fn pick(self) -> Result<Option<Box<dyn Command + Send + Sync>>, LumenError> {
+        let shas = match self.config.picker {
+            PickerKind::Builtin => {
+                picker::pick(&self.repo, PickerEntry::load(&self.repo, &self.filter)?)?
+            }
+            PickerKind::Fzf => LumenCommand::get_shas_from_fzf(&self.repo, &self.filter)?,
+        };
 
-pub struct ListCommand;
+        let mut commits = shas
+            .into_iter()
+            .map(|sha| Commit::new(&self.repo, sha))
+            .collect::<Result<Vec<_>, _>>()?;
 
-#[async_trait]
-impl Command for ListCommand {
-    async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
-        let sha = LumenCommand::get_sha_from_fzf()?;
-        let git_entity = GitEntity::Commit(Commit::new(sha)?);
-        ExplainCommand {
-            git_entity,
-            query: None,
-        }
-        .execute(provider)
-        .await
+        Ok(match commits.len() {
+            0 => None,
+            1 => Some(Box::new(ExplainCommand {
+                git_entity: GitEntity::Commit(commits.remove(0)),
+                query: None,
+            })),
+            _ => Some(Box::new(MultiExplainCommand {
+                commits,
+                query: None,
+            })),
+        })
     }
 }
@@ -0,0 +1,389 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    config::configuration::DraftConfig,
+    error::LumenError,
+    git_entity::{commit::MergeDiff, diff::Diff, repo::Repo, GitEntity},
+    output::{EntityDetails, GenerationReport},
+    provider::{LumenProvider, TokenUsage},
+    server::{
+        self,
+        jsonrpc::{self, to_result},
+        Handler, RpcError,
+    },
+};
+
+use super::{draft::DraftCommand, explain::ExplainCommand};
+
+/// Protocol revisions this server speaks, newest first
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Model Context Protocol server offering lumen's git tools to AI agents
+pub struct McpServer {
+    provider: LumenProvider,
+    draft_config: DraftConfig,
+    repo: Repo,
+}
+
+#[derive(Deserialize)]
+struct InitializeParams {
+    #[serde(rename = "protocolVersion")]
+    protocol_version: String,
+}
+
+#[derive(Deserialize)]
+struct CallParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExplainCommitArgs {
+    commit: String,
+    query: Option<String>,
+    #[serde(default)]
+    merge_diff: MergeDiff,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExplainRangeArgs {
+    from: String,
+    to: String,
+    #[serde(default)]
+    triple_dot: bool,
+    query: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DraftArgs {
+    context: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoArgs {}
+
+/// `structuredContent` of the tools calling the provider
+#[derive(Serialize)]
+struct ToolReport {
+    text: String,
+    entity: Option<EntityDetails>,
+    usage: Option<TokenUsage>,
+}
+
+impl From<GenerationReport> for ToolReport {
+    fn from(report: GenerationReport) -> Self {
+        ToolReport {
+            text: report.text,
+            entity: report.entity,
+            usage: report.usage,
+        }
+    }
+}
+
+/// Input and output schemas of every tool, as listed by `tools/list`
+fn tools() -> Value {
+    let query = json!({
+        "type": "string",
+        "description": "Question to answer about the changes instead of summarizing them"
+    });
+    let report = json!({
+        "type": "object",
+        "properties": {
+            "text": { "type": "string", "description": "Markdown generated by the model" },
+            "entity": {
+                "type": ["object", "null"],
+                "description": "What was explained, `type` is `commit`, `range`, `working_tree`, `working_tree_all`, `stash` or `tag`"
+            },
+            "usage": {
+                "type": ["object", "null"],
+                "properties": {
+                    "prompt_tokens": { "type": "integer" },
+                    "completion_tokens": { "type": "integer" },
+                    "total_tokens": { "type": "integer" }
+                }
+            }
+        },
+        "required": ["text"]
+    });
+
+    json!([
+        {
+            "name": "explain_commit",
+            "description": "Explain what a commit, stash or tag changes and why, from its message and diff. A tag is explained with everything since the previous tag.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "commit": {
+                        "type": "string",
+                        "description": "Commit hash or ref, eg: HEAD~2, a stash, eg: stash@{0}, or a tag"
+                    },
+                    "query": query,
+                    "merge_diff": {
+                        "type": "string",
+                        "enum": ["first-parent", "combined"],
+                        "description": "For merge commits, diff against the first parent (default) or only show conflict resolutions"
+                    }
+                },
+                "required": ["commit"],
+                "additionalProperties": false
+            },
+            "outputSchema": report
+        },
+        {
+            "name": "explain_range",
+            "description": "Explain the combined changes between two commits",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "from": { "type": "string", "description": "Start of the range, eg: main" },
+                    "to": { "type": "string", "description": "End of the range, eg: HEAD" },
+                    "triple_dot": {
+                        "type": "boolean",
+                        "description": "Compare `to` with its merge base with `from`, like `from...to`"
+                    },
+                    "query": query
+                },
+                "required": ["from", "to"],
+                "additionalProperties": false
+            },
+            "outputSchema": report
+        },
+        {
+            "name": "draft_commit_message",
+            "description": "Draft a conventional commit message for the staged changes",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "context": { "type": "string", "description": "Intent of the change, to guide the message" }
+                },
+                "additionalProperties": false
+            },
+            "outputSchema": report
+        },
+        {
+            "name": "get_staged_diff",
+            "description": "Return the diff of the staged changes, without calling a model",
+            "inputSchema": {
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": { "diff": { "type": "string" } },
+                "required": ["diff"]
+            }
+        }
+    ])
+}
+
+impl McpServer {
+    pub fn new(provider: LumenProvider, draft_config: DraftConfig, repo: Repo) -> Self {
+        McpServer {
+            provider,
+            draft_config,
+            repo,
+        }
+    }
+
+    /// Agrees on the client's protocol revision when this server speaks it
+    fn initialize(&self, params: InitializeParams) -> Value {
+        let version = PROTOCOL_VERSIONS
+            .into_iter()
+            .find(|version| *version == params.protocol_version)
+            .unwrap_or(PROTOCOL_VERSIONS[0]);
+
+        json!({
+            "protocolVersion": version,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "lumen", "version": env!("CARGO_PKG_VERSION") }
+        })
+    }
+
+    async fn explain(
+        &self,
+        git_entity: GitEntity,
+        query: Option<String>,
+    ) -> Result<Value, RpcError> {
+        let command = ExplainCommand { git_entity, query };
+        to_result(ToolReport::from(command.generate(&self.provider).await?))
+    }
+
+    /// Runs `task`, git commands, on the blocking thread pool
+    async fn git<T, F>(&self, task: F) -> Result<T, RpcError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Repo) -> Result<T, LumenError> + Send + 'static,
+    {
+        let repo = self.repo.clone();
+        server::blocking(move || Ok(task(&repo)?)).await
+    }
+
+    /// Structured result of a tool. Failures of lumen come back as `LUMEN_ERROR`, other
+    /// codes mean the call itself was malformed.
+    async fn run_tool(&self, name: &str, arguments: Value) -> Result<Value, RpcError> {
+        match name {
+            "explain_commit" => {
+                let args: ExplainCommitArgs = jsonrpc::params(arguments)?;
+                let (commit, merge_diff) = (args.commit, args.merge_diff);
+                let git_entity = self
+                    .git(move |repo| GitEntity::from_reference(repo, &commit, merge_diff))
+                    .await?;
+                self.explain(git_entity, args.query).await
+            }
+            "explain_range" => {
+                let args: ExplainRangeArgs = jsonrpc::params(arguments)?;
+                let (from, to, triple_dot) = (args.from, args.to, args.triple_dot);
+                let diff = self
+                    .git(move |repo| Diff::from_commits_range(repo, &from, &to, triple_dot))
+                    .await?;
+                self.explain(GitEntity::Diff(diff), args.query).await
+            }
+            "draft_commit_message" => {
+                let args: DraftArgs = jsonrpc::params(arguments)?;
+                let diff = self.git(|repo| Diff::from_working_tree(repo, true)).await?;
+                let command = DraftCommand {
+                    git_entity: GitEntity::Diff(diff),
+                    context: args.context,
+                    draft_config: self.draft_config.clone(),
+                };
+                to_result(ToolReport::from(command.generate(&self.provider).await?))
+            }
+            "get_staged_diff" => {
+                let NoArgs {} = jsonrpc::params(arguments)?;
+                let Diff::WorkingTree { diff, .. } =
+                    self.git(|repo| Diff::from_working_tree(repo, true)).await?
+                else {
+                    unreachable!("the working tree diff is a `WorkingTree`")
+                };
+                Ok(json!({ "diff": diff }))
+            }
+            _ => Err(RpcError::invalid_params(format!("unknown tool `{name}`"))),
+        }
+    }
+
+    /// Failures of lumen itself are reported in the result, so the agent can see and
+    /// react to them, eg: nothing is staged
+    async fn call_tool(&self, params: CallParams) -> Result<Value, RpcError> {
+        let (text, structured, is_error) = match self.run_tool(&params.name, params.arguments).await
+        {
+            Ok(structured) => {
+                let text = match structured.get("text").or_else(|| structured.get("diff")) {
+                    Some(Value::String(text)) => text.clone(),
+                    _ => structured.to_string(),
+                };
+                (text, Some(structured), false)
+            }
+            Err(e) if e.code == jsonrpc::LUMEN_ERROR => (e.message, None, true),
+            Err(e) => return Err(e),
+        };
+
+        let mut result = json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error
+        });
+        if let Some(structured) = structured {
+            result["structuredContent"] = structured;
+        }
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Handler for McpServer {
+    async fn handle(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(self.initialize(jsonrpc::params(params)?)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools() })),
+            "tools/call" => self.call_tool(jsonrpc::params(params)?).await,
+            // Notifications such as `notifications/initialized` need no action
+            method if method.starts_with("notifications/") => Ok(Value::Null),
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+}
+
+pub async fn serve(handler: McpServer) -> Result<(), LumenError> {
+    server::serve_stdio(Arc::new(handler)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_provider, TempRepo};
+
+    fn server(temp: &TempRepo) -> McpServer {
+        McpServer::new(mock_provider(), DraftConfig::default(), temp.repo())
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_list_tools() {
+        let temp = TempRepo::new();
+        let result = server(&temp)
+            .handle("initialize", json!({ "protocolVersion": "2024-11-05" }))
+            .await
+            .unwrap();
+        assert_eq!(result["protocolVersion"], "2024-11-05");
+        assert!(result["capabilities"]["tools"].is_object());
+
+        let result = server(&temp)
+            .handle("tools/list", Value::Null)
+            .await
+            .unwrap();
+        let names: Vec<&str> = result["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "explain_commit",
+                "explain_range",
+                "draft_commit_message",
+                "get_staged_diff"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_call_tool() {
+        let temp = TempRepo::new();
+        let result = server(&temp)
+            .handle(
+                "tools/call",
+                json!({ "name": "explain_commit", "arguments": { "commit": "HEAD" } }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["isError"], false);
+        assert_eq!(result["content"][0]["text"], "Mock response from mock");
+        assert_eq!(result["structuredContent"]["entity"]["type"], "commit");
+
+        let result = server(&temp)
+            .handle(
+                "tools/call",
+                json!({ "name": "explain_commit", "arguments": { "commit": "not-a-commit" } }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["isError"], true);
+
+        let error = server(&temp)
+            .handle("tools/call", json!({ "name": "rm_rf", "arguments": {} }))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, jsonrpc::INVALID_PARAMS);
+    }
+}
@@ -2,17 +2,31 @@ use async_trait::async_trait;
 use draft::DraftCommand;
 use explain::ExplainCommand;
 use list::ListCommand;
+use models::ModelsCommand;
+use multi_explain::MultiExplainCommand;
+use spinoff::{spinners, Color, Spinner, Streams};
 use std::process::Stdio;
 
-use crate::config::configuration::DraftConfig;
+use crate::config::cli::{LogFilter, OutputFormat};
+use crate::config::configuration::{DraftConfig, ListConfig};
 use crate::error::LumenError;
+use crate::git_entity::commit::Commit;
 use crate::git_entity::diff::Diff;
+use crate::git_entity::repo::Repo;
 use crate::git_entity::GitEntity;
-use crate::provider::LumenProvider;
+use crate::output::{GenerationReport, Printer, PromptPreview};
+use crate::provider::{LumenProvider, ModelInfo};
 
+pub mod auth;
+pub mod config;
+pub mod doctor;
 pub mod draft;
 pub mod explain;
 pub mod list;
+pub mod mcp;
+pub mod models;
+pub mod multi_explain;
+pub mod serve;
 
 #[derive(Debug)]
 pub enum CommandType {
@@ -20,50 +34,139 @@ pub enum CommandType {
         git_entity: GitEntity,
         query: Option<String>,
     },
-    List,
-    Draft(Option<String>, DraftConfig),
+    ExplainMany {
+        commits: Vec<Commit>,
+        query: Option<String>,
+    },
+    List(ListConfig, LogFilter, Repo),
+    Draft(Option<String>, DraftConfig, Repo),
+    Models,
+}
+
+/// What a command produced, printed by `Printer::render`
+#[derive(Debug, Clone)]
+pub enum CommandOutput {
+    /// A drafted commit message
+    Draft(GenerationReport),
+    /// Explanation of a single commit or diff
+    Explanation {
+        git_entity: GitEntity,
+        report: GenerationReport,
+    },
+    /// Each commit's explanation, then a summary of how they relate
+    MultiExplanation {
+        explanations: Vec<(Commit, GenerationReport)>,
+        summary: GenerationReport,
+    },
+    /// Models offered by the provider, and the configured one
+    Models {
+        current: String,
+        models: Vec<ModelInfo>,
+    },
 }
 
 #[async_trait]
 pub trait Command {
-    async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError>;
+    async fn execute(&self, provider: &LumenProvider) -> Result<CommandOutput, LumenError>;
+
+    /// The prompts `execute` would send, for `--dry-run`
+    fn preview(&self, provider: &LumenProvider) -> Result<Vec<PromptPreview>, LumenError>;
+
+    /// Spinner text shown on the terminal while `execute` runs, none for quick commands
+    fn progress(&self, _provider: &LumenProvider) -> Option<String> {
+        None
+    }
 }
 
 impl CommandType {
-    pub fn create_command(self) -> Result<Box<dyn Command>, LumenError> {
-        Ok(match self {
+    /// `None` when there is nothing to do, eg: no commit was picked from the list
+    pub fn create_command(self) -> Result<Option<Box<dyn Command + Send + Sync>>, LumenError> {
+        Ok(Some(match self {
             CommandType::Explain { git_entity, query } => {
                 Box::new(ExplainCommand { git_entity, query })
             }
-            CommandType::List => Box::new(ListCommand),
-            CommandType::Draft(context, draft_config) => Box::new(DraftCommand {
-                git_entity: GitEntity::Diff(Diff::from_working_tree(true)?),
+            CommandType::ExplainMany { commits, query } => {
+                Box::new(MultiExplainCommand { commits, query })
+            }
+            CommandType::List(config, filter, repo) => {
+                return ListCommand {
+                    config,
+                    filter,
+                    repo,
+                }
+                .pick()
+            }
+            CommandType::Draft(context, draft_config, repo) => Box::new(DraftCommand {
+                git_entity: GitEntity::Diff(Diff::from_working_tree(&repo, true)?),
                 draft_config,
                 context,
             }),
-        })
+            CommandType::Models => Box::new(ModelsCommand),
+        }))
     }
 }
 
 pub struct LumenCommand {
     provider: LumenProvider,
+    printer: Printer,
+    dry_run: bool,
 }
 
 impl LumenCommand {
-    pub fn new(provider: LumenProvider) -> Self {
-        LumenCommand { provider }
+    pub fn new(provider: LumenProvider, printer: Printer, dry_run: bool) -> Self {
+        LumenCommand {
+            provider,
+            printer,
+            dry_run,
+        }
     }
 
     pub async fn execute(&self, command_type: CommandType) -> Result<(), LumenError> {
-        command_type.create_command()?.execute(&self.provider).await
+        let Some(command) = command_type.create_command()? else {
+            return Ok(());
+        };
+        if self.dry_run {
+            let prompts = command.preview(&self.provider)?;
+            return self.printer.dry_run(&self.provider, &prompts);
+        }
+
+        let output = self.run(command.as_ref()).await?;
+        self.printer.render(&self.provider, &output)
     }
 
-    fn get_sha_from_fzf() -> Result<String, LumenError> {
-        let command = "git log --color=always --format='%C(auto)%h%d %s %C(black)%C(bold)%cr' | fzf --ansi --reverse --bind='enter:become(echo {1})'";
+    /// Runs `command` behind a spinner when printing to the terminal
+    async fn run(
+        &self,
+        command: &(dyn Command + Send + Sync),
+    ) -> Result<CommandOutput, LumenError> {
+        let progress = match self.printer.format() {
+            OutputFormat::Text => command.progress(&self.provider),
+            OutputFormat::Json => None,
+        };
+        let Some(progress) = progress else {
+            return command.execute(&self.provider).await;
+        };
+
+        let mut spinner =
+            Spinner::new_with_stream(spinners::Dots, progress, Color::Blue, Streams::Stderr);
+        let output = command.execute(&self.provider).await;
+        match output {
+            Ok(_) => spinner.success("Done"),
+            Err(_) => spinner.clear(),
+        }
+        output
+    }
+
+    fn get_shas_from_fzf(repo: &Repo, filter: &LogFilter) -> Result<Vec<String>, LumenError> {
+        // Filter arguments are passed as positional parameters to avoid shell quoting issues
+        let command = "git log --color=always --format='%C(auto)%h%d %s %C(black)%C(bold)%cr' \"$@\" | fzf --ansi --reverse --multi --preview='git show --stat --color=always {1}' --bind='enter:become(printf \"%s\\n\" {+1})'";
 
         let output = std::process::Command::new("sh")
             .arg("-c")
             .arg(command)
+            .arg("sh")
+            .args(filter.git_log_args())
+            .current_dir(repo.root())
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -74,9 +177,9 @@ impl LumenCommand {
             stderr.pop();
 
             let hint = match &stderr {
-                stderr if stderr.contains("fzf: command not found") => {
-                    Some("`list` command requires fzf")
-                }
+                stderr if stderr.contains("fzf: command not found") => Some(
+                    "`list` is configured to use fzf, install it or set \"picker\": \"builtin\"",
+                ),
                 _ => None,
             };
 
@@ -88,33 +191,11 @@ impl LumenCommand {
             return Err(LumenError::CommandError(format!("{} {}", stderr, hint)));
         }
 
-        let mut sha = String::from_utf8(output.stdout)?;
-        sha.pop(); // remove trailing newline from echo
+        let shas = String::from_utf8(output.stdout)?
+            .lines()
+            .map(str::to_string)
+            .collect();
 
-        Ok(sha)
-    }
-
-    fn print_with_mdcat(content: String) -> Result<(), LumenError> {
-        match std::process::Command::new("mdcat")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-        {
-            Ok(mut mdcat) => {
-                if let Some(stdin) = mdcat.stdin.take() {
-                    std::process::Command::new("echo")
-                        .arg(&content)
-                        .stdout(stdin)
-                        .spawn()?
-                        .wait()?;
-                }
-                let output = mdcat.wait_with_output()?;
-                println!("{}", String::from_utf8(output.stdout)?);
-            }
-            Err(_) => {
-                println!("{}", content);
-            }
-        }
-        Ok(())
+        Ok(shas)
     }
 }
@@ -0,0 +1,27 @@
+use async_trait::async_trait;
+
+use crate::{error::LumenError, output::PromptPreview, provider::LumenProvider};
+
+use super::{Command, CommandOutput};
+
+/// Lists the models offered by the configured provider, marking the one in use
+pub struct ModelsCommand;
+
+#[async_trait]
+impl Command for ModelsCommand {
+    async fn execute(&self, provider: &LumenProvider) -> Result<CommandOutput, LumenError> {
+        Ok(CommandOutput::Models {
+            current: provider.model().to_string(),
+            models: provider.list_models().await?,
+        })
+    }
+
+    /// Listing models sends no prompt
+    fn preview(&self, _provider: &LumenProvider) -> Result<Vec<PromptPreview>, LumenError> {
+        Ok(Vec::new())
+    }
+
+    fn progress(&self, provider: &LumenProvider) -> Option<String> {
+        Some(format!("Listing {} models...", provider.name()))
+    }
+}
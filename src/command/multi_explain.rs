@@ -0,0 +1,130 @@
+use std::time::Instant;
+
+use async_trait::async_trait;
+use futures::future::try_join_all;
+
+use crate::{
+    ai_prompt::AIPrompt,
+    error::LumenError,
+    git_entity::{commit::Commit, GitEntity},
+    output::{GenerationReport, PromptKind, PromptPreview},
+    provider::{LumenProvider, ProviderError},
+};
+
+use super::{explain::ExplainCommand, Command, CommandOutput};
+
+/// Explains each commit on its own, then asks for an aggregate narrative of how they relate
+pub struct MultiExplainCommand {
+    pub commits: Vec<Commit>,
+    pub query: Option<String>,
+}
+
+impl MultiExplainCommand {
+    fn commands(&self) -> Vec<ExplainCommand> {
+        self.commits
+            .iter()
+            .map(|commit| ExplainCommand {
+                git_entity: GitEntity::Commit(commit.clone()),
+                query: None,
+            })
+            .collect()
+    }
+
+    /// Returns one report per commit followed by the aggregate report
+    pub async fn generate(
+        &self,
+        provider: &LumenProvider,
+    ) -> Result<Vec<GenerationReport>, LumenError> {
+        let commands = self.commands();
+
+        let mut reports =
+            try_join_all(commands.iter().map(|command| command.generate(provider))).await?;
+        let explanations: Vec<String> = reports.iter().map(|report| report.text.clone()).collect();
+
+        let started = Instant::now();
+        let summary = provider.explain_many(self, &explanations).await?;
+        reports.push(GenerationReport::new(
+            PromptKind::Aggregate,
+            None,
+            self.query.clone(),
+            summary,
+            started.elapsed(),
+        ));
+
+        Ok(reports)
+    }
+}
+
+#[async_trait]
+impl Command for MultiExplainCommand {
+    async fn execute(&self, provider: &LumenProvider) -> Result<CommandOutput, LumenError> {
+        let mut reports = self.generate(provider).await?;
+        let summary = reports
+            .pop()
+            .expect("generate returns the aggregate report last");
+
+        Ok(CommandOutput::MultiExplanation {
+            explanations: self.commits.iter().cloned().zip(reports).collect(),
+            summary,
+        })
+    }
+
+    /// The aggregate prompt gets placeholders where the per-commit explanations would go
+    fn preview(&self, provider: &LumenProvider) -> Result<Vec<PromptPreview>, LumenError> {
+        let mut previews = self
+            .commands()
+            .iter()
+            .map(|command| command.prompt_preview(provider))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let placeholders: Vec<String> = (1..=self.commits.len())
+            .map(|index| format!("<explanation {index} from the previous requests>"))
+            .collect();
+        previews.push(PromptPreview::new(
+            PromptKind::Aggregate,
+            None,
+            self.query.clone(),
+            AIPrompt::build_multi_explain_prompt(self, &placeholders)
+                .map_err(ProviderError::from)?,
+            provider,
+        ));
+        Ok(previews)
+    }
+
+    fn progress(&self, _provider: &LumenProvider) -> Option<String> {
+        Some(format!("Explaining {} commits...", self.commits.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_provider, TempRepo};
+
+    #[tokio::test]
+    async fn test_one_report_per_commit_then_the_aggregate() {
+        let temp = TempRepo::new();
+        temp.write("src/lib.rs", "pub fn hello() {}\n");
+        temp.git(&["add", "src/lib.rs"]);
+        temp.git(&["commit", "-q", "-m", "Add lib"]);
+        let repo = temp.repo();
+        let command = MultiExplainCommand {
+            commits: vec![
+                Commit::new(&repo, "HEAD".to_string()).unwrap(),
+                Commit::new(&repo, "HEAD~1".to_string()).unwrap(),
+            ],
+            query: None,
+        };
+
+        let reports = command.generate(&mock_provider()).await.unwrap();
+        let kinds: Vec<PromptKind> = reports.iter().map(|report| report.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                PromptKind::Explain,
+                PromptKind::Explain,
+                PromptKind::Aggregate
+            ]
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    commit_reference::CommitReference,
+    config::configuration::DraftConfig,
+    error::LumenError,
+    git_entity::{commit::MergeDiff, diff::Diff, repo::Repo, GitEntity},
+    provider::LumenProvider,
+    server::{
+        self,
+        jsonrpc::{self, to_result},
+        Handler, RpcError,
+    },
+};
+
+use super::{draft::DraftCommand, explain::ExplainCommand};
+
+/// Question asked by `review`, an explanation focused on what a reviewer would flag
+const REVIEW_QUERY: &str = "Review these changes. Point out bugs, risky edits, missing error handling and missing tests, most important first, quoting the lines concerned. Say so if nothing stands out.";
+
+/// Answers editor requests with the provider built once at startup, so its HTTP client
+/// and the configuration stay warm between requests
+pub struct Server {
+    provider: LumenProvider,
+    draft_config: DraftConfig,
+    repo: Repo,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DraftParams {
+    context: Option<String>,
+}
+
+/// What `explain` and `review` look at: one of `commit`, `range` or `diff`
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TargetParams {
+    /// Also a stash, eg: `stash@{0}`, or a tag
+    commit: Option<String>,
+    /// `from..to` or `from...to`, as on the command line
+    range: Option<String>,
+    diff: bool,
+    staged: bool,
+    /// With `diff`, staged and unstaged changes together
+    all: bool,
+    /// With `all`, also untracked files
+    untracked: bool,
+    query: Option<String>,
+    merge_diff: MergeDiff,
+}
+
+impl TargetParams {
+    fn git_entity(&self, repo: &Repo) -> Result<GitEntity, RpcError> {
+        let reference = match (&self.commit, &self.range, self.diff) {
+            (Some(commit), None, false) => CommitReference::Single(commit.clone()),
+            (None, Some(range), false) => range
+                .parse()
+                .map_err(|e| RpcError::invalid_params(format!("invalid `range`: {e}")))?,
+            (None, None, true) if self.all => {
+                return Ok(GitEntity::Diff(Diff::from_working_tree_all(
+                    repo,
+                    self.untracked,
+                )?))
+            }
+            (None, None, true) => {
+                return Ok(GitEntity::Diff(Diff::from_working_tree(repo, self.staged)?))
+            }
+            _ => {
+                return Err(RpcError::invalid_params(
+                    "expected exactly one of `commit`, `range` or `diff`",
+                ))
+            }
+        };
+
+        Ok(match reference {
+            CommitReference::Single(reference) => {
+                GitEntity::from_reference(repo, &reference, self.merge_diff)?
+            }
+            CommitReference::Range { from, to } => {
+                GitEntity::Diff(Diff::from_commits_range(repo, &from, &to, false)?)
+            }
+            CommitReference::TripleDots { from, to } => {
+                GitEntity::Diff(Diff::from_commits_range(repo, &from, &to, true)?)
+            }
+        })
+    }
+}
+
+impl Server {
+    pub fn new(provider: LumenProvider, draft_config: DraftConfig, repo: Repo) -> Self {
+        Server {
+            provider,
+            draft_config,
+            repo,
+        }
+    }
+
+    async fn draft(&self, params: DraftParams) -> Result<Value, RpcError> {
+        let repo = self.repo.clone();
+        let diff = server::blocking(move || Ok(Diff::from_working_tree(&repo, true)?)).await?;
+        let command = DraftCommand {
+            git_entity: GitEntity::Diff(diff),
+            context: params.context,
+            draft_config: self.draft_config.clone(),
+        };
+        to_result(command.generate(&self.provider).await?)
+    }
+
+    async fn explain(&self, mut params: TargetParams) -> Result<Value, RpcError> {
+        let query = params.query.take();
+        let repo = self.repo.clone();
+        let git_entity = server::blocking(move || params.git_entity(&repo)).await?;
+        let command = ExplainCommand { git_entity, query };
+        to_result(command.generate(&self.provider).await?)
+    }
+
+    /// `query` narrows the review down, eg: "focus on concurrency"
+    async fn review(&self, params: TargetParams) -> Result<Value, RpcError> {
+        let query = match &params.query {
+            Some(focus) => format!("{REVIEW_QUERY} Focus on: {focus}"),
+            None => REVIEW_QUERY.to_string(),
+        };
+        self.explain(TargetParams {
+            query: Some(query),
+            ..params
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl Handler for Server {
+    async fn handle(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "draft" => self.draft(jsonrpc::params(params)?).await,
+            "explain" => self.explain(jsonrpc::params(params)?).await,
+            "review" => self.review(jsonrpc::params(params)?).await,
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+}
+
+/// Serves over stdio, or over HTTP on `http` when given
+pub async fn serve(handler: Server, http: Option<&str>) -> Result<(), LumenError> {
+    let handler = Arc::new(handler);
+    match http {
+        Some(addr) => server::serve_http(handler, addr).await,
+        None => server::serve_stdio(handler).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_provider, TempRepo};
+    use serde_json::json;
+
+    fn server(temp: &TempRepo) -> Server {
+        Server::new(mock_provider(), DraftConfig::default(), temp.repo())
+    }
+
+    #[tokio::test]
+    async fn test_review_commit() {
+        let temp = TempRepo::new();
+        let result = server(&temp)
+            .handle("review", json!({ "commit": "HEAD" }))
+            .await
+            .unwrap();
+
+        assert_eq!(result["kind"], "explain");
+        assert_eq!(result["entity"]["type"], "commit");
+        assert_eq!(result["text"], "Mock response from mock");
+        assert!(result["query"]
+            .as_str()
+            .unwrap()
+            .starts_with("Review these changes."));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_target() {
+        let temp = TempRepo::new();
+        let error = server(&temp)
+            .handle("explain", json!({ "commit": "HEAD", "diff": true }))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, jsonrpc::INVALID_PARAMS);
+
+        let error = server(&temp)
+            .handle("explain", json!({ "sha": "HEAD" }))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, jsonrpc::INVALID_PARAMS);
+    }
+}
@@ -1,31 +1,112 @@
-use clap::{command, Parser, Subcommand, ValueEnum};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use crate::commit_reference::CommitReference;
+use crate::config::suggest;
+use crate::git_entity::commit::MergeDiff;
 
 #[derive(Parser)]
 #[command(name = "lumen")]
 #[command(about = "AI-powered CLI tool for git commit summaries", long_about = None)]
 #[command(version)]
 pub struct Cli {
-    /// Path to configuration file eg: ./path/to/lumen.config.json
+    /// Path to configuration file eg: ./path/to/lumen.config.json, its values override the
+    /// other config files and environment variables
     #[arg(long)]
     pub config: Option<String>,
 
-    #[arg(value_enum, short = 'p', long = "provider")]
+    /// Run in the repository containing this directory instead of the working directory
+    #[arg(short = 'C', long = "repo", value_name = "DIR", global = true)]
+    pub repo: Option<PathBuf>,
+
+    #[arg(value_enum, short = 'p', long = "provider", global = true)]
     pub provider: Option<ProviderType>,
 
-    #[arg(short = 'k', long = "api-key")]
+    #[arg(short = 'k', long = "api-key", global = true)]
     pub api_key: Option<String>,
 
-    #[arg(short = 'm', long = "model")]
+    #[arg(short = 'm', long = "model", global = true)]
     pub model: Option<String>,
 
     #[command(subcommand)]
     pub command: Commands,
 
-    #[arg(long = "api-base")]
+    #[arg(long = "api-base", global = true)]
     pub api_base_url: Option<String>,
+
+    /// Named profile from the config file, eg: `fast` or `private`
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Sampling temperature, lower is more deterministic
+    #[arg(long, global = true)]
+    pub temperature: Option<f64>,
+
+    /// Maximum number of tokens to generate
+    #[arg(long, global = true)]
+    pub max_tokens: Option<u32>,
+
+    /// Nucleus sampling probability mass
+    #[arg(long, global = true)]
+    pub top_p: Option<f64>,
+
+    /// Stop generating at this sequence (can be repeated)
+    #[arg(long, global = true)]
+    pub stop: Vec<String>,
+
+    /// Output format, `json` prints a machine-readable document instead of markdown
+    #[arg(value_enum, long, default_value_t = OutputFormat::Text, global = true)]
+    pub format: OutputFormat,
+
+    /// Print markdown as-is instead of rendering it for the terminal
+    #[arg(long, global = true)]
+    pub plain: bool,
+
+    /// Log more details to stderr, repeat for more (`-v` requests, `-vv` git commands and config)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Also write logs as JSON lines to this file
+    #[arg(long, env = "LUMEN_LOG_FILE", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Print the prompts and request payloads instead of sending them
+    #[arg(long, visible_alias = "show-prompt", global = true)]
+    pub dry_run: bool,
+}
+
+impl Cli {
+    /// `command` without any flag, so configuration comes from files, env and the keyring
+    pub fn new(command: Commands) -> Self {
+        Cli {
+            config: None,
+            repo: None,
+            provider: None,
+            api_key: None,
+            model: None,
+            command,
+            api_base_url: None,
+            profile: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            stop: Vec::new(),
+            format: OutputFormat::Text,
+            plain: false,
+            verbose: 0,
+            log_file: None,
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
@@ -37,6 +118,49 @@ pub enum ProviderType {
     Ollama,
     Openrouter,
     Gemini,
+    /// Offline stand-in answering from a fixtures file, for tests
+    Mock,
+}
+
+impl ProviderType {
+    pub const NAMES: [&'static str; 8] = [
+        "openai",
+        "phind",
+        "groq",
+        "claude",
+        "ollama",
+        "openrouter",
+        "gemini",
+        "mock",
+    ];
+
+    pub fn requires_api_key(self) -> bool {
+        !matches!(
+            self,
+            ProviderType::Phind | ProviderType::Ollama | ProviderType::Mock
+        )
+    }
+
+    pub fn requires_model(self) -> bool {
+        matches!(self, ProviderType::Ollama)
+    }
+
+    /// Highest `temperature` the provider's API accepts, the lowest is always 0
+    pub fn max_temperature(self) -> f64 {
+        match self {
+            ProviderType::Claude => 1.0,
+            _ => 2.0,
+        }
+    }
+}
+
+impl fmt::Display for ProviderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self
+            .to_possible_value()
+            .expect("every provider has a command line value");
+        f.write_str(value.get_name())
+    }
 }
 
 impl FromStr for ProviderType {
@@ -51,7 +175,12 @@ impl FromStr for ProviderType {
             "ollama" => Ok(ProviderType::Ollama),
             "openrouter" => Ok(ProviderType::Openrouter),
             "gemini" => Ok(ProviderType::Gemini),
-            _ => Err(format!("Unknown provider: {}", s)),
+            "mock" => Ok(ProviderType::Mock),
+            _ => Err(format!(
+                "Unknown provider: {}{}",
+                s,
+                suggest::hint(s, ProviderType::NAMES)
+            )),
         }
     }
 }
@@ -60,9 +189,10 @@ impl FromStr for ProviderType {
 pub enum Commands {
     /// Explain the changes in a commit, or the current diff
     Explain {
-        /// The commit hash to use
+        /// The commit hash to use, or several hashes to explain together. Also takes a stash,
+        /// eg: stash@{0}, or a tag, explained with everything since the previous tag
         #[arg(group = "target", value_parser = clap::value_parser!(CommitReference))]
-        reference: Option<CommitReference>,
+        references: Vec<CommitReference>,
 
         /// Explain current diff
         #[arg(long, group = "target")]
@@ -72,16 +202,217 @@ pub enum Commands {
         #[arg(long)]
         staged: bool,
 
+        /// Explain staged and unstaged changes together, in separate sections
+        #[arg(long, requires = "diff", conflicts_with = "staged")]
+        all: bool,
+
+        /// With `--all`, also include the content of untracked files, files over 32 KiB are
+        /// only named with their size
+        #[arg(long, requires = "all")]
+        untracked: bool,
+
         /// Ask a question instead of summary
         #[arg(short, long)]
         query: Option<String>,
+
+        /// What to show of a merge commit
+        #[arg(long, value_enum, default_value_t)]
+        merge_diff: MergeDiff,
     },
     /// List all commits in an interactive fuzzy-finder, and summarize the changes
-    List,
+    List(LogFilter),
     /// Generate a commit message for the staged changes
     Draft {
         /// Add context to communicate intent
         #[arg(short, long)]
         context: Option<String>,
     },
+    /// List the models offered by the provider, `*` marks the one in use
+    Models,
+    /// Check git, tools, configuration and the provider connection
+    Doctor,
+    /// Serve draft, explain and review to editors over JSON-RPC on stdio
+    Serve {
+        /// Listen for JSON-RPC over HTTP on this address instead, eg: 127.0.0.1:7878
+        #[arg(long, value_name = "ADDR")]
+        http: Option<String>,
+    },
+    /// Serve explain, draft and staged diff tools to AI agents over MCP on stdio
+    Mcp,
+    /// Manage API keys stored in the system keyring
+    Auth {
+        #[command(subcommand)]
+        action: AuthCommand,
+    },
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuthCommand {
+    /// Store the API key of a provider in the system keyring
+    Login {
+        #[arg(value_enum)]
+        provider: ProviderType,
+    },
+    /// Remove the stored API key of a provider
+    Logout {
+        #[arg(value_enum)]
+        provider: ProviderType,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print every effective configuration value
+    Show {
+        /// Also print where each value came from
+        #[arg(long)]
+        origin: bool,
+    },
+    /// Interactively create a config file
+    Init {
+        /// Write `.lumen.toml` at the repository root instead of the global config
+        #[arg(long)]
+        local: bool,
+    },
+    /// Check config files for errors, unknown keys and missing API keys
+    Validate {
+        /// File to check, defaults to every discovered config file
+        path: Option<String>,
+    },
+}
+
+/// Options narrowing down the commits shown by `list`, passed through to `git log`
+#[derive(Args, Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Only commits whose author matches the pattern
+    #[arg(long)]
+    pub author: Option<String>,
+
+    /// Only commits more recent than the date, eg: "2 weeks ago" or 2024-01-31
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only commits older than the date
+    #[arg(long)]
+    pub until: Option<String>,
+
+    /// Only commits touching the path (can be repeated)
+    #[arg(long)]
+    pub path: Vec<String>,
+
+    /// Only commits whose message matches the pattern
+    #[arg(long)]
+    pub grep: Option<String>,
+
+    /// List commits of this branch instead of the current one
+    #[arg(long, conflicts_with = "all", value_parser = revision)]
+    pub branch: Option<String>,
+
+    /// List commits of all branches
+    #[arg(long)]
+    pub all: bool,
+
+    /// Maximum number of commits to list
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+}
+
+/// A branch or other revision, never read as an option by git, eg: `--output=<file>`
+fn revision(value: &str) -> Result<String, String> {
+    if value.starts_with('-') {
+        return Err(format!("`{value}` is not a revision, it starts with `-`"));
+    }
+    Ok(value.to_string())
+}
+
+impl LogFilter {
+    pub fn git_log_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(author) = &self.author {
+            args.push(format!("--author={author}"));
+        }
+        if let Some(since) = &self.since {
+            args.push(format!("--since={since}"));
+        }
+        if let Some(until) = &self.until {
+            args.push(format!("--until={until}"));
+        }
+        if let Some(grep) = &self.grep {
+            args.push(format!("--grep={grep}"));
+        }
+        if let Some(limit) = self.limit {
+            args.push(format!("--max-count={limit}"));
+        }
+        if self.all {
+            args.push("--all".to_string());
+        } else if let Some(branch) = &self.branch {
+            args.push(branch.clone());
+        }
+        if !self.path.is_empty() {
+            args.push("--".to_string());
+            args.extend(self.path.iter().cloned());
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_filter_has_no_args() {
+        assert!(LogFilter::default().git_log_args().is_empty());
+    }
+
+    #[test]
+    fn test_filter_args() {
+        let cli = Cli::try_parse_from([
+            "lumen",
+            "list",
+            "--author",
+            "alice",
+            "--since",
+            "1 week ago",
+            "--path",
+            "src/provider",
+            "--branch",
+            "develop",
+            "-n",
+            "20",
+        ])
+        .unwrap();
+
+        let Commands::List(filter) = cli.command else {
+            panic!("expected list command");
+        };
+        assert_eq!(
+            filter.git_log_args(),
+            vec![
+                "--author=alice",
+                "--since=1 week ago",
+                "--max-count=20",
+                "develop",
+                "--",
+                "src/provider",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branch_cannot_be_an_option() {
+        assert!(Cli::try_parse_from(["lumen", "list", "--branch=--output=/tmp/x"]).is_err());
+    }
+
+    #[test]
+    fn test_branch_conflicts_with_all() {
+        assert!(Cli::try_parse_from(["lumen", "list", "--all", "--branch", "main"]).is_err());
+    }
 }
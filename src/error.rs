@@ -3,6 +3,7 @@ use crate::{
     provider::ProviderError,
 };
 use std::io;
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,7 +14,10 @@ pub enum LumenError {
     #[error("{0}")]
     GitDiffError(#[from] DiffError),
 
-    #[error("Missing API key for {0}, use --api-key or LUMEN_API_KEY env variable, or add \"api_key\": \"...\" to configuration file")]
+    #[error("{} is not inside a git repository, run lumen from one or point at it with -C <dir>", .0.display())]
+    NotARepository(PathBuf),
+
+    #[error("Missing API key for {0}, use --api-key or LUMEN_API_KEY env variable, add \"api_key\" or \"api_key_cmd\" to configuration file, or run `lumen auth login`")]
     MissingApiKey(String),
 
     #[error("Missing Model for {0}, use --model or LUMEN_MODEL env variable, or add \"model\": \"...\" to configuration file")]
@@ -36,4 +40,43 @@ pub enum LumenError {
 
     #[error(transparent)]
     ProviderError(#[from] ProviderError),
+
+    #[error(transparent)]
+    SerializationError(#[from] serde_json::Error),
+}
+
+impl LumenError {
+    /// Stable identifier of the error, used in `--format json` output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LumenError::GitCommitError(_) => "git_commit",
+            LumenError::GitDiffError(_) => "git_diff",
+            LumenError::NotARepository(_) => "not_a_repository",
+            LumenError::MissingApiKey(_) => "missing_api_key",
+            LumenError::MissingModel(_) => "missing_model",
+            LumenError::InvalidArguments(_) => "invalid_arguments",
+            LumenError::InvalidConfiguration(_) => "invalid_configuration",
+            LumenError::IoError(_) => "io",
+            LumenError::Utf8Error(_) => "utf8",
+            LumenError::CommandError(_) => "command",
+            LumenError::ProviderError(_) => "provider",
+            LumenError::SerializationError(_) => "serialization",
+        }
+    }
+
+    /// Process exit code, distinct per class of failure so scripts can react to them
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LumenError::InvalidArguments(_) | LumenError::InvalidConfiguration(_) => 2,
+            LumenError::MissingApiKey(_) | LumenError::MissingModel(_) => 3,
+            LumenError::GitCommitError(_)
+            | LumenError::GitDiffError(_)
+            | LumenError::NotARepository(_) => 4,
+            LumenError::ProviderError(_) => 5,
+            LumenError::IoError(_)
+            | LumenError::Utf8Error(_)
+            | LumenError::CommandError(_)
+            | LumenError::SerializationError(_) => 1,
+        }
+    }
 }
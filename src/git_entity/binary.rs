@@ -0,0 +1,274 @@
+use std::path::Path;
+
+use super::repo::Repo;
+
+/// Kind of file whose diff says nothing to a model, named by its extension
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum AssetKind {
+    Image,
+    Font,
+    Audio,
+    Video,
+    Archive,
+    Document,
+}
+
+impl AssetKind {
+    fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+        Some(match extension.as_str() {
+            "png" | "jpg" | "jpeg" | "gif" | "bmp" | "ico" | "webp" | "tif" | "tiff" | "svg"
+            | "avif" | "heic" | "psd" => AssetKind::Image,
+            "ttf" | "otf" | "woff" | "woff2" | "eot" => AssetKind::Font,
+            "mp3" | "wav" | "ogg" | "flac" | "m4a" | "aac" => AssetKind::Audio,
+            "mp4" | "mov" | "avi" | "mkv" | "webm" => AssetKind::Video,
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "7z" | "rar" | "jar" => {
+                AssetKind::Archive
+            }
+            "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" => AssetKind::Document,
+            _ => return None,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            AssetKind::Image => "image",
+            AssetKind::Font => "font",
+            AssetKind::Audio => "audio",
+            AssetKind::Video => "video",
+            AssetKind::Archive => "archive",
+            AssetKind::Document => "document",
+        }
+    }
+}
+
+/// `diff` with the patch of each binary file and asset replaced by a line giving its path,
+/// kind and size change. Their content would only waste tokens, eg: an SVG's path data.
+pub fn summarize(repo: &Repo, diff: &str) -> String {
+    let mut starts: Vec<usize> = diff
+        .match_indices("\ndiff --")
+        .map(|(index, _)| index + 1)
+        .collect();
+    if diff.starts_with("diff --") {
+        starts.insert(0, 0);
+    }
+
+    // Anything before the first file, eg: the summary of `--compact-summary`
+    let mut summarized = diff[..starts.first().copied().unwrap_or(diff.len())].to_string();
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(diff.len());
+        let file = &diff[start..end];
+        match summarize_file(repo, file) {
+            Some(summary) => summarized.push_str(&summary),
+            None => summarized.push_str(file),
+        }
+    }
+    summarized
+}
+
+/// The header of a file's diff followed by its summary, `None` for a text file
+fn summarize_file(repo: &Repo, file: &str) -> Option<String> {
+    let lines: Vec<&str> = file.lines().collect();
+    let binary = lines.iter().any(|line| {
+        *line == "GIT binary patch"
+            || line.starts_with("Binary files ") && line.ends_with(" differ")
+    });
+    let path = path(&lines)?;
+    let kind = AssetKind::from_path(&path);
+    if !binary && kind.is_none() {
+        return None;
+    }
+
+    // Mode, rename and index lines stay, they are short and tell what happened to the file
+    let header_end = lines
+        .iter()
+        .position(|line| {
+            line.starts_with("--- ")
+                || line.starts_with("@@")
+                || line.starts_with("Binary files ")
+                || *line == "GIT binary patch"
+        })
+        .unwrap_or(lines.len());
+
+    let (old_size, new_size) = sizes(repo, &lines, &path);
+    let added = lines.iter().any(|line| line.starts_with("new file mode"));
+    let deleted = lines
+        .iter()
+        .any(|line| line.starts_with("deleted file mode"));
+    let change = match (added, deleted, old_size, new_size) {
+        (true, _, _, Some(new)) => format!("added, {}", format_size(new)),
+        (_, true, Some(old), _) => format!("deleted, {}", format_size(old)),
+        (true, _, _, _) => "added".to_string(),
+        (_, true, _, _) => "deleted".to_string(),
+        (_, _, Some(old), Some(new)) => format!(
+            "changed, {} -> {} ({})",
+            format_size(old),
+            format_size(new),
+            format_delta(new as i64 - old as i64)
+        ),
+        _ => "changed".to_string(),
+    };
+    let kind = kind.map_or("binary", |kind| kind.name());
+
+    let mut summary = lines[..header_end].join("\n");
+    summary.push_str(&format!("\nBinary file {path} ({kind}) {change}\n"));
+    Some(summary)
+}
+
+/// Path of the file after the change, or before it for a deleted file. The `Binary files`
+/// line is the last resort, its paths can't be told apart when one contains " and ".
+fn path(lines: &[&str]) -> Option<String> {
+    let strip = |path: &str| {
+        path.strip_prefix("a/")
+            .or_else(|| path.strip_prefix("b/"))
+            .unwrap_or(path)
+            .to_string()
+    };
+    let line = |prefix: &str| {
+        lines
+            .iter()
+            .filter_map(|line| line.strip_prefix(prefix))
+            .find(|path| *path != "/dev/null")
+    };
+
+    if let Some(path) = line("rename to ") {
+        return Some(path.to_string());
+    }
+    if let Some(path) = line("+++ ").or_else(|| line("--- ")) {
+        return Some(strip(path));
+    }
+    if let Some(path) = line("diff --cc ").or_else(|| line("diff --combined ")) {
+        return Some(path.to_string());
+    }
+    if let Some(paths) = line("diff --git ") {
+        // Without a rename both sides are the same path, which may contain " b/" itself
+        let half = paths.len().saturating_sub(5) / 2;
+        if let (Some(old), Some(new)) = (paths.get(2..2 + half), paths.get(5 + half..)) {
+            if old == new && paths.starts_with("a/") && paths[2 + half..].starts_with(" b/") {
+                return Some(new.to_string());
+            }
+        }
+        if let Some((_, path)) = paths.rsplit_once(" b/") {
+            return Some(path.to_string());
+        }
+    }
+
+    let (old, new) = line("Binary files ")?
+        .strip_suffix(" differ")?
+        .split_once(" and ")?;
+    Some(strip(if new == "/dev/null" { old } else { new }))
+}
+
+/// Sizes of the file before and after the change from the blobs on its `index` line,
+/// `None` when it did not exist or the blob is unknown
+fn sizes(repo: &Repo, lines: &[&str], path: &str) -> (Option<u64>, Option<u64>) {
+    let Some((old, new)) = lines
+        .iter()
+        .find_map(|line| line.strip_prefix("index "))
+        .and_then(|blobs| blobs.split_whitespace().next())
+        .and_then(|blobs| blobs.split_once(".."))
+    else {
+        return (None, None);
+    };
+
+    let blob_size = |blob: &str| {
+        if blob.bytes().all(|b| b == b'0') {
+            return None;
+        }
+        let output = repo.git(&["cat-file", "-s", blob]).ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+            .flatten()
+    };
+
+    // For a combined diff, the first parent
+    let old = old.split(',').next().and_then(blob_size);
+    // The working tree's version of a file is not in the object database
+    let new = blob_size(new).or_else(|| {
+        (!new.bytes().all(|b| b == b'0'))
+            .then(|| std::fs::metadata(repo.root().join(path)).ok())
+            .flatten()
+            .map(|metadata| metadata.len())
+    });
+    (old, new)
+}
+
+pub(super) fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn format_delta(delta: i64) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!("{sign}{}", format_size(delta.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    #[test]
+    fn test_summarize_binary_and_assets() {
+        let repo = TempRepo::new().repo();
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1 @@
+-old
++new
+diff --git a/logo.png b/logo.png
+new file mode 100644
+index 0000000..3333333
+Binary files /dev/null and b/logo.png differ
+diff --git a/icon.svg b/icon.svg
+deleted file mode 100644
+index 4444444..0000000
+--- a/icon.svg
++++ /dev/null
+@@ -1 +0,0 @@
+-<svg><path d=\"M0 0h24v24H0z\"/></svg>
+";
+
+        let summarized = summarize(&repo, diff);
+        assert!(summarized.contains("+new\n"));
+        assert!(summarized.contains(
+            "new file mode 100644\nindex 0000000..3333333\nBinary file logo.png (image) added\n"
+        ));
+        assert!(
+            summarized.ends_with("index 4444444..0000000\nBinary file icon.svg (image) deleted\n")
+        );
+        assert!(!summarized.contains("<svg>"));
+    }
+
+    #[test]
+    fn test_path_containing_and() {
+        let lines = [
+            "diff --git a/cats and dogs.png b/cats and dogs.png",
+            "new file mode 100644",
+            "index 0000000..3333333",
+            "Binary files /dev/null and b/cats and dogs.png differ",
+        ];
+        assert_eq!(path(&lines).unwrap(), "cats and dogs.png");
+
+        let lines = [
+            "diff --git a/x b/y.png b/x b/y.png",
+            "Binary files a/x b/y.png and b/x b/y.png differ",
+        ];
+        assert_eq!(path(&lines).unwrap(), "x b/y.png");
+    }
+
+    #[test]
+    fn test_format_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(12_800), "12.5 KiB");
+        assert_eq!(format_delta(-3_145_728), "-3.0 MiB");
+    }
+}
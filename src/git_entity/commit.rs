@@ -1,5 +1,7 @@
+use super::repo::Repo;
 use crate::error::LumenError;
-use std::process::Command;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Error, Debug, Clone)]
@@ -9,6 +11,30 @@ pub enum CommitError {
 
     #[error("Diff for commit '{0}' is empty")]
     EmptyDiff(String),
+
+    #[error("Stash '{0}' not found")]
+    InvalidStash(String),
+}
+
+/// What the diff of a merge commit shows
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeDiff {
+    /// Changes brought in relative to the first parent, eg: what merging a branch added
+    #[default]
+    FirstParent,
+    /// Only lines differing from every parent, eg: conflict resolutions
+    Combined,
+}
+
+impl MergeDiff {
+    /// Tells the model what the diff it is given covers
+    pub fn describe(&self) -> &'static str {
+        match self {
+            MergeDiff::FirstParent => "diff against the first parent, the changes the merge brought in",
+            MergeDiff::Combined => "combined diff, only lines that differ from every parent such as conflict resolutions",
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -19,25 +45,44 @@ pub struct Commit {
     pub author_name: String,
     pub author_email: String,
     pub date: String,
+    /// Committer date in seconds since the epoch, `date` is in the committer's time zone
+    pub timestamp: i64,
+    pub parents: Vec<String>,
+    /// How `diff` was made, set for merge commits only
+    pub merge_diff: Option<MergeDiff>,
 }
 
 impl Commit {
-    pub fn new(sha: String) -> Result<Self, LumenError> {
-        Self::is_valid_commit(&sha)?;
+    pub fn new(repo: &Repo, sha: String) -> Result<Self, LumenError> {
+        Self::with_merge_diff(repo, sha, MergeDiff::default())
+    }
+
+    /// Like `new`, with `merge_diff` choosing the diff if the commit is a merge
+    pub fn with_merge_diff(
+        repo: &Repo,
+        sha: String,
+        merge_diff: MergeDiff,
+    ) -> Result<Self, LumenError> {
+        Self::is_valid_commit(repo, &sha)?;
+
+        let parents = Self::get_parents(repo, &sha)?;
+        let merge_diff = (parents.len() > 1).then_some(merge_diff);
 
         Ok(Commit {
-            full_hash: Self::get_full_hash(&sha)?,
-            message: Self::get_message(&sha)?,
-            diff: Self::get_diff(&sha)?,
-            author_name: Self::get_author_name(&sha)?,
-            author_email: Self::get_author_email(&sha)?,
-            date: Self::get_date(&sha)?,
+            full_hash: Self::get_full_hash(repo, &sha)?,
+            message: Self::get_message(repo, &sha)?,
+            diff: Self::get_diff(repo, &sha, merge_diff)?,
+            author_name: Self::get_author_name(repo, &sha)?,
+            author_email: Self::get_author_email(repo, &sha)?,
+            date: Self::get_date(repo, &sha)?,
+            timestamp: Self::get_timestamp(repo, &sha)?,
+            parents,
+            merge_diff,
         })
     }
 
-    pub fn is_valid_commit(sha: &str) -> Result<(), LumenError> {
-        let output = Command::new("git").args(["cat-file", "-t", sha]).output()?;
-        let output_str = String::from_utf8(output.stdout)?;
+    pub fn is_valid_commit(repo: &Repo, sha: &str) -> Result<(), LumenError> {
+        let output_str = repo.git_text(&["cat-file", "-t", sha])?;
 
         if output_str.trim() == "commit" {
             return Ok(());
@@ -46,27 +91,31 @@ impl Commit {
         Err(CommitError::InvalidCommit(sha.to_string()).into())
     }
 
-    fn get_full_hash(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git").args(["rev-parse", sha]).output()?;
-
-        let mut full_hash = String::from_utf8(output.stdout)?;
+    fn get_full_hash(repo: &Repo, sha: &str) -> Result<String, LumenError> {
+        let mut full_hash = repo.git_text(&["rev-parse", sha])?;
         full_hash.pop(); // Remove trailing newline
         Ok(full_hash)
     }
 
-    fn get_diff(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args([
-                "diff-tree",
-                "-p",
-                "--binary",
-                "--no-color",
-                "--compact-summary",
-                sha,
-            ])
-            .output()?;
-
-        let diff = String::from_utf8(output.stdout)?;
+    fn get_parents(repo: &Repo, sha: &str) -> Result<Vec<String>, LumenError> {
+        let parents = repo.git_text(&["log", "--format=%P", "-n", "1", sha])?;
+        Ok(parents.split_whitespace().map(String::from).collect())
+    }
+
+    fn get_diff(
+        repo: &Repo,
+        sha: &str,
+        merge_diff: Option<MergeDiff>,
+    ) -> Result<String, LumenError> {
+        let first_parent = format!("{sha}^1");
+        let mut args = vec!["diff-tree", "-p", "--no-color", "--compact-summary"];
+        match merge_diff {
+            // Plain `diff-tree` shows nothing for merges
+            None => args.push(sha),
+            Some(MergeDiff::FirstParent) => args.extend([first_parent.as_str(), sha]),
+            Some(MergeDiff::Combined) => args.extend(["--cc", sha]),
+        }
+        let diff = repo.git_diff(&args)?;
         if diff.is_empty() {
             return Err(CommitError::EmptyDiff(sha.to_string()).into());
         }
@@ -74,53 +123,44 @@ impl Commit {
         Ok(diff)
     }
 
-    fn get_message(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args(["log", "--format=%B", "-n", "1", sha])
-            .output()?;
-
-        let mut message = String::from_utf8(output.stdout)?;
+    fn get_message(repo: &Repo, sha: &str) -> Result<String, LumenError> {
+        let mut message = repo.git_text(&["log", "--format=%B", "-n", "1", sha])?;
         message.pop(); // Remove trailing newline
         if message.ends_with('\n') {
-            message.pop();  // Remove the second trailing newline in commits where it exists (the ones not from github GUI)
-        }        
+            message.pop(); // Remove the second trailing newline in commits where it exists (the ones not from github GUI)
+        }
         Ok(message)
     }
 
-    fn get_author_name(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args(["log", "--format=%an", "-n", "1", sha])
-            .output()?;
-
-        let mut name = String::from_utf8(output.stdout)?;
+    fn get_author_name(repo: &Repo, sha: &str) -> Result<String, LumenError> {
+        let mut name = repo.git_text(&["log", "--format=%an", "-n", "1", sha])?;
         name.pop(); // Remove trailing newline
         Ok(name)
     }
 
-    fn get_author_email(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args(["log", "--format=%ae", "-n", "1", sha])
-            .output()?;
-
-        let mut email = String::from_utf8(output.stdout)?;
+    fn get_author_email(repo: &Repo, sha: &str) -> Result<String, LumenError> {
+        let mut email = repo.git_text(&["log", "--format=%ae", "-n", "1", sha])?;
         email.pop(); // Remove trailing newline
         Ok(email)
     }
 
-    fn get_date(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args([
-                "log",
-                "--format=%cd",
-                "--date=format:%Y-%m-%d %H:%M:%S",
-                "-n",
-                "1",
-                sha,
-            ])
-            .output()?;
-
-        let mut date = String::from_utf8(output.stdout)?;
+    fn get_date(repo: &Repo, sha: &str) -> Result<String, LumenError> {
+        let mut date = repo.git_text(&[
+            "log",
+            "--format=%cd",
+            "--date=format:%Y-%m-%d %H:%M:%S",
+            "-n",
+            "1",
+            sha,
+        ])?;
         date.pop(); // Remove trailing newline
         Ok(date)
     }
+
+    fn get_timestamp(repo: &Repo, sha: &str) -> Result<i64, LumenError> {
+        let timestamp = repo.git_text(&["log", "--format=%ct", "-n", "1", sha])?;
+        timestamp.trim().parse().map_err(|_| {
+            LumenError::CommandError(format!("invalid commit date `{}`", timestamp.trim()))
+        })
+    }
 }
@@ -1,7 +1,11 @@
 use crate::error::LumenError;
 use thiserror::Error;
 
-use super::commit::Commit;
+use super::{binary::format_size, commit::Commit, repo::Repo};
+
+/// Untracked files bigger than this are summarized in one line instead of sent whole, eg:
+/// a log or a generated dump left in the working tree
+const MAX_UNTRACKED_SIZE: u64 = 32 * 1024;
 
 #[derive(Error, Debug)]
 pub enum DiffError {
@@ -20,19 +24,24 @@ pub enum Diff {
         to: String,
         diff: String,
     },
+    /// Staged and unstaged changes kept apart, so the model can tell what would be committed
+    WorkingTreeAll {
+        staged: String,
+        unstaged: String,
+        /// Files git does not track yet, as new files, `None` unless asked for
+        untracked: Option<String>,
+    },
 }
 
 impl Diff {
-    pub fn from_working_tree(staged: bool) -> Result<Self, LumenError> {
+    pub fn from_working_tree(repo: &Repo, staged: bool) -> Result<Self, LumenError> {
         let args = if staged {
             vec!["diff", "--staged"]
         } else {
             vec!["diff"]
         };
 
-        let output = std::process::Command::new("git").args(args).output()?;
-
-        let diff = String::from_utf8(output.stdout)?;
+        let diff = repo.git_diff(&args)?;
         if diff.is_empty() {
             return Err(DiffError::EmptyDiff { staged }.into());
         }
@@ -40,18 +49,19 @@ impl Diff {
         Ok(Diff::WorkingTree { staged, diff })
     }
 
-    pub fn from_commits_range(from: &str, to: &str, triple_dot: bool) -> Result<Self, LumenError> {
-        let _ = Commit::is_valid_commit(from)?;
-        let _ = Commit::is_valid_commit(to)?;
+    pub fn from_commits_range(
+        repo: &Repo,
+        from: &str,
+        to: &str,
+        triple_dot: bool,
+    ) -> Result<Self, LumenError> {
+        Commit::is_valid_commit(repo, from)?;
+        Commit::is_valid_commit(repo, to)?;
 
         let separator = if triple_dot { "..." } else { ".." };
         let range = format!("{}{}{}", from, separator, to);
 
-        let output = std::process::Command::new("git")
-            .args(["diff", &range])
-            .output()?;
-
-        let diff = String::from_utf8(output.stdout)?;
+        let diff = repo.git_diff(&["diff", &range])?;
 
         if diff.is_empty() {
             return Err(DiffError::EmptyDiff { staged: false }.into());
@@ -63,4 +73,69 @@ impl Diff {
             diff,
         })
     }
+
+    /// Every change in the working tree, with the content of untracked files if `untracked`
+    pub fn from_working_tree_all(repo: &Repo, untracked: bool) -> Result<Self, LumenError> {
+        let staged = repo.git_diff(&["diff", "--staged"])?;
+        let unstaged = repo.git_diff(&["diff"])?;
+        let untracked = untracked.then(|| Self::untracked_files(repo)).transpose()?;
+
+        if staged.is_empty()
+            && unstaged.is_empty()
+            && untracked.as_deref().is_none_or(str::is_empty)
+        {
+            return Err(DiffError::EmptyDiff { staged: false }.into());
+        }
+
+        Ok(Diff::WorkingTreeAll {
+            staged,
+            unstaged,
+            untracked,
+        })
+    }
+
+    /// Untracked files that are not ignored, each diffed against nothing, see
+    /// `MAX_UNTRACKED_SIZE`
+    fn untracked_files(repo: &Repo) -> Result<String, LumenError> {
+        let paths = repo.git_text(&["ls-files", "--others", "--exclude-standard", "-z"])?;
+
+        let mut diff = String::new();
+        for path in paths.split('\0').filter(|path| !path.is_empty()) {
+            let size = std::fs::symlink_metadata(repo.root().join(path)).map(|m| m.len());
+            if let Ok(size @ MAX_UNTRACKED_SIZE..) = size {
+                diff.push_str(&format!(
+                    "diff --git a/{path} b/{path}\nnew file mode 100644\n\
+                     Untracked file {path} added, {}, content left out\n",
+                    format_size(size)
+                ));
+                continue;
+            }
+            // Exits with 1 as the files differ
+            diff.push_str(&repo.git_diff(&["diff", "--no-index", "--", "/dev/null", path])?);
+        }
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    #[test]
+    fn test_large_untracked_file_is_summarized() {
+        let temp = TempRepo::new();
+        temp.write("notes.md", "a small note\n");
+        temp.write("dump.log", &"a line of a large log\n".repeat(4096));
+
+        let Diff::WorkingTreeAll { untracked, .. } =
+            Diff::from_working_tree_all(&temp.repo(), true).unwrap()
+        else {
+            panic!("expected the whole working tree");
+        };
+        let untracked = untracked.unwrap();
+        assert!(untracked.contains("+a small note\n"));
+        assert!(untracked.contains("Untracked file dump.log added, 88.0 KiB, content left out\n"));
+        assert!(!untracked.contains("a line of a large log"));
+    }
 }
@@ -1,19 +1,68 @@
-use commit::Commit;
+use crate::error::LumenError;
+use commit::{Commit, MergeDiff};
 use diff::Diff;
 use indoc::formatdoc;
+use repo::Repo;
+use stash::Stash;
+use tag::Tag;
 
+pub mod binary;
 pub mod commit;
 pub mod diff;
+pub mod repo;
+pub mod stash;
+pub mod tag;
 
 #[derive(Debug, Clone)]
 pub enum GitEntity {
     Commit(Commit),
     Diff(Diff),
+    Stash(Stash),
+    Tag(Tag),
 }
 
 impl GitEntity {
+    /// A stash, tag or commit as named on the command line, eg: `stash@{1}`, `v1.2.0` or `HEAD~2`
+    pub fn from_reference(
+        repo: &Repo,
+        reference: &str,
+        merge_diff: MergeDiff,
+    ) -> Result<Self, LumenError> {
+        if Stash::is_stash(reference) {
+            return Ok(GitEntity::Stash(Stash::new(repo, reference)?));
+        }
+        if Tag::is_tag(repo, reference) {
+            return Ok(GitEntity::Tag(Tag::new(repo, reference)?));
+        }
+        Ok(GitEntity::Commit(Commit::with_merge_diff(
+            repo,
+            reference.to_string(),
+            merge_diff,
+        )?))
+    }
+
     pub fn format_static_details(&self) -> String {
         match self {
+            GitEntity::Commit(
+                commit @ Commit {
+                    merge_diff: Some(merge_diff),
+                    ..
+                },
+            ) => formatdoc! {"
+                # Entity: Merge Commit
+                `commit {hash}` | {author} <{email}> | {date}
+                Parents: {parents} | {merge_diff}
+
+                {message}
+                -----",
+                hash = commit.full_hash,
+                author = commit.author_name,
+                email = commit.author_email,
+                date = commit.date,
+                parents = commit.parents.iter().map(|parent| format!("`{parent}`")).collect::<Vec<_>>().join(", "),
+                merge_diff = merge_diff.describe(),
+                message = commit.message,
+            },
             GitEntity::Commit(commit) => formatdoc! {"
                 # Entity: Commit
                 `commit {hash}` | {author} <{email}> | {date}
@@ -30,10 +79,36 @@ impl GitEntity {
                 # Entity: Working Tree Diff{staged}",
                 staged = if *staged { " (staged)" } else { "" }
             },
+            GitEntity::Diff(Diff::WorkingTreeAll { untracked, .. }) => formatdoc! {"
+                # Entity: Working Tree Diff (staged, unstaged{untracked})",
+                untracked = if untracked.is_some() { " and untracked" } else { "" }
+            },
             GitEntity::Diff(Diff::CommitsRange { from, to, .. }) => formatdoc! {"
                 # Entity: Range
                 `{from}` -> `{to}`
             "},
+            GitEntity::Stash(stash) => formatdoc! {"
+                # Entity: Stash
+                `{reference}` | `{hash}` | {date}
+
+                {message}
+                -----",
+                reference = stash.reference,
+                hash = stash.full_hash,
+                date = stash.date,
+                message = stash.message,
+            },
+            GitEntity::Tag(tag) => formatdoc! {"
+                # Entity: Tag
+                `{previous}` -> `{name}` | {date}
+
+                {message}
+                -----",
+                previous = tag.previous.as_deref().unwrap_or("(first tag)"),
+                name = tag.name,
+                date = tag.date,
+                message = tag.message,
+            },
         }
     }
 }
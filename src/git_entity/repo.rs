@@ -0,0 +1,120 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::Instant;
+
+use super::binary;
+use crate::error::LumenError;
+
+/// A git repository, every git command runs at its root so lumen works from any
+/// subdirectory, or from outside with `-C <dir>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repo {
+    root: PathBuf,
+    /// Directory lumen was pointed at, relative to `root`, eg: `src/`. Empty at the root.
+    prefix: String,
+}
+
+impl Repo {
+    /// The repository containing `dir`, or the working directory when `None`
+    pub fn discover(dir: Option<&Path>) -> Result<Self, LumenError> {
+        let dir = match dir {
+            Some(dir) => dir.to_path_buf(),
+            None => std::env::current_dir()?,
+        };
+
+        let args = ["rev-parse", "--show-toplevel", "--show-prefix"];
+        let output = git_in(&dir, &args)?;
+        if !output.status.success() {
+            return Err(LumenError::NotARepository(dir));
+        }
+
+        let output = decode(output.stdout, &args);
+        let mut lines = output.lines();
+        Ok(Repo {
+            root: PathBuf::from(lines.next().unwrap_or_default()),
+            prefix: lines.next().unwrap_or_default().to_string(),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// `path` given relative to the directory lumen was pointed at, as git expects it at
+    /// the root. Absolute paths and pathspec magic, eg: `:/src`, are left as they are.
+    pub fn pathspec(&self, path: &str) -> String {
+        if path.starts_with(':') || Path::new(path).is_absolute() {
+            return path.to_string();
+        }
+        format!("{}{path}", self.prefix)
+    }
+
+    /// Runs git with `args` at the repository root
+    pub fn git(&self, args: &[&str]) -> io::Result<Output> {
+        git_in(&self.root, args)
+    }
+
+    /// Output of git with `args`, see `decode`
+    pub fn git_text(&self, args: &[&str]) -> Result<String, LumenError> {
+        Ok(decode(self.git(args)?.stdout, args))
+    }
+
+    /// Like `git_text` for a command printing a diff, with binary files and assets
+    /// replaced by a one-line summary
+    pub fn git_diff(&self, args: &[&str]) -> Result<String, LumenError> {
+        Ok(binary::summarize(self, &self.git_text(args)?))
+    }
+}
+
+/// Output of git with `args` as text. Files and commits in other encodings, eg: Latin-1,
+/// are decoded lossily with a warning instead of failing the whole command.
+pub fn decode(bytes: Vec<u8>, args: &[&str]) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|e| {
+        tracing::warn!(
+            command = %format!("git {}", args.join(" ")),
+            "git printed invalid UTF-8, the invalid bytes were replaced"
+        );
+        String::from_utf8_lossy(e.as_bytes()).into_owned()
+    })
+}
+
+/// Runs `git -C <dir>` with `args`, logging the command line, its exit status and duration
+fn git_in(dir: &Path, args: &[&str]) -> io::Result<Output> {
+    let started = Instant::now();
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output();
+    let command = format!("git -C {} {}", dir.display(), args.join(" "));
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+
+    match &output {
+        Ok(output) => {
+            tracing::debug!(%command, status = output.status.code(), elapsed_ms, "ran git")
+        }
+        Err(e) => tracing::debug!(%command, error = %e, "could not run git"),
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    #[test]
+    fn test_discover_from_subdirectory() {
+        let temp = TempRepo::new();
+        temp.write("src/lib.rs", "");
+        let root = temp.repo();
+        let subdirectory = Repo::discover(Some(&temp.path().join("src"))).unwrap();
+        assert_eq!(root.root(), subdirectory.root());
+        assert_eq!(root.pathspec("src/lib.rs"), "src/lib.rs");
+        assert_eq!(subdirectory.pathspec("lib.rs"), "src/lib.rs");
+        assert_eq!(subdirectory.pathspec(":/README.md"), ":/README.md");
+
+        let outside = std::env::temp_dir().join(format!("lumen-no-repo-{}", std::process::id()));
+        std::fs::create_dir_all(&outside).unwrap();
+        let error = Repo::discover(Some(&outside)).unwrap_err();
+        std::fs::remove_dir_all(&outside).unwrap();
+        assert!(matches!(error, LumenError::NotARepository(_)));
+    }
+}
@@ -0,0 +1,83 @@
+use super::{commit::CommitError, repo::Repo};
+use crate::error::LumenError;
+
+/// A stash entry, eg: `stash@{0}`, with the changes it saved
+#[derive(Clone, Debug)]
+pub struct Stash {
+    pub reference: String,
+    pub full_hash: String,
+    /// `WIP on <branch>: ...` or the message given to `git stash push -m`
+    pub message: String,
+    pub date: String,
+    pub diff: String,
+}
+
+impl Stash {
+    pub fn is_stash(reference: &str) -> bool {
+        reference.starts_with("stash@{") && reference.ends_with('}')
+    }
+
+    pub fn new(repo: &Repo, reference: &str) -> Result<Self, LumenError> {
+        let output = repo.git(&["rev-parse", "--verify", "--quiet", reference])?;
+        if !output.status.success() {
+            return Err(CommitError::InvalidStash(reference.to_string()).into());
+        }
+        let full_hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        let details = repo.git_text(&[
+            "log",
+            "--format=%s%n%cd",
+            "--date=format:%Y-%m-%d %H:%M:%S",
+            "-n",
+            "1",
+            reference,
+        ])?;
+        let mut lines = details.lines();
+        let message = lines.next().unwrap_or_default().to_string();
+        let date = lines.next().unwrap_or_default().to_string();
+
+        // Against the commit the stash was made on, as `git stash show` does, with the files
+        // saved by `git stash -u`. Git before 2.32 rejects `--include-untracked` and prints
+        // nothing, it then shows the tracked changes only.
+        let show = |untracked: &[&str]| {
+            let mut args = vec!["stash", "show", "-p", "--no-color", "--compact-summary"];
+            args.extend(untracked);
+            args.push(reference);
+            repo.git_diff(&args)
+        };
+        let mut diff = show(&["--include-untracked"])?;
+        if diff.is_empty() {
+            diff = show(&[])?;
+        }
+        if diff.is_empty() {
+            return Err(CommitError::EmptyDiff(reference.to_string()).into());
+        }
+
+        Ok(Stash {
+            reference: reference.to_string(),
+            full_hash,
+            message,
+            date,
+            diff,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    #[test]
+    fn test_stash_shows_untracked_files() {
+        let temp = TempRepo::new();
+        temp.write("README.md", "# Hello, stash\n");
+        temp.write("notes.md", "a new note\n");
+        temp.git(&["stash", "push", "-q", "-u", "-m", "notes"]);
+
+        let stash = Stash::new(&temp.repo(), "stash@{0}").unwrap();
+        assert!(stash.message.ends_with(": notes"));
+        assert!(stash.diff.contains("+# Hello, stash\n"));
+        assert!(stash.diff.contains("+a new note\n"));
+    }
+}
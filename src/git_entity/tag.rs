@@ -0,0 +1,94 @@
+use super::{
+    commit::CommitError,
+    repo::{self, Repo},
+};
+use crate::error::LumenError;
+
+/// Commit subjects listed in the prompt, the diff already has the details
+const MAX_COMMITS: &str = "100";
+
+/// A tag and everything since the previous one, like the notes of a release
+#[derive(Clone, Debug)]
+pub struct Tag {
+    pub name: String,
+    /// Commit the tag points to
+    pub full_hash: String,
+    /// Nearest older tag, `None` for the first tag, which is compared with an empty tree
+    pub previous: Option<String>,
+    /// Annotation of the tag, empty for lightweight tags
+    pub message: String,
+    pub date: String,
+    /// `<short hash> <subject>` of the commits since `previous`, newest first
+    pub commits: Vec<String>,
+    pub diff: String,
+}
+
+impl Tag {
+    pub fn is_tag(repo: &Repo, name: &str) -> bool {
+        let tag_ref = format!("refs/tags/{name}");
+        repo.git(&["rev-parse", "--verify", "--quiet", &tag_ref])
+            .is_ok_and(|output| output.status.success())
+    }
+
+    pub fn new(repo: &Repo, name: &str) -> Result<Self, LumenError> {
+        let tag_ref = format!("refs/tags/{name}");
+        let commit = format!("{tag_ref}^{{commit}}");
+
+        let full_hash = repo.git_text(&["rev-parse", &commit])?.trim().to_string();
+
+        let details = repo.git_text(&[
+            "for-each-ref",
+            "--format=%(objecttype)%00%(creatordate:format:%Y-%m-%d %H:%M:%S)%00%(contents)",
+            &tag_ref,
+        ])?;
+        let mut fields = details.splitn(3, '\0');
+        let annotated = fields.next() == Some("tag");
+        let date = fields.next().unwrap_or_default().to_string();
+        // A lightweight tag's contents are the commit message, not an annotation
+        let message = match fields.next() {
+            Some(contents) if annotated => contents.trim().to_string(),
+            _ => String::new(),
+        };
+
+        let parent = format!("{commit}^");
+        let args = ["describe", "--tags", "--abbrev=0", &parent];
+        let output = repo.git(&args)?;
+        let previous = output
+            .status
+            .success()
+            .then(|| repo::decode(output.stdout, &args).trim().to_string());
+
+        let from = match &previous {
+            Some(previous) => format!("refs/tags/{previous}"),
+            None => repo
+                .git_text(&["hash-object", "-t", "tree", "/dev/null"])?
+                .trim()
+                .to_string(),
+        };
+
+        let range = match &previous {
+            Some(_) => format!("{from}..{commit}"),
+            None => commit.clone(),
+        };
+        let commits = repo
+            .git_text(&["log", "--format=%h %s", "-n", MAX_COMMITS, &range])?
+            .lines()
+            .map(String::from)
+            .collect();
+
+        let diff = repo.git_diff(&["diff", "--no-color", "--compact-summary", &from, &commit])?;
+        if diff.is_empty() {
+            return Err(CommitError::EmptyDiff(name.to_string()).into());
+        }
+
+        Ok(Tag {
+            name: name.to_string(),
+            full_hash,
+            previous,
+            message,
+            date,
+            commits,
+            diff,
+        })
+    }
+}
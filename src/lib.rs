@@ -0,0 +1,52 @@
+//! Explains commits and diffs and drafts commit messages with AI providers.
+//!
+//! The `lumen` binary is a thin layer over this crate. The items re-exported here are the
+//! supported API, the modules also hold the command line's own plumbing.
+//!
+//! ```no_run
+//! use lumen::{Cli, Commands, DraftCommand, Diff, GitEntity, LumenConfig, LumenProvider, Repo};
+//!
+//! # async fn run() -> Result<(), lumen::LumenError> {
+//! // Config files, env variables and the keyring, as `lumen draft` would see them
+//! let config = LumenConfig::build(&Cli::new(Commands::Draft { context: None }))?;
+//! let provider = LumenProvider::from_config(&config)?;
+//!
+//! let repo = Repo::discover(None)?;
+//! let command = DraftCommand {
+//!     git_entity: GitEntity::Diff(Diff::from_working_tree(&repo, true)?),
+//!     context: None,
+//!     draft_config: config.draft,
+//! };
+//! let report = command.generate(&provider).await?;
+//! println!("{}", report.text);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod ai_prompt;
+pub mod command;
+pub mod commit_reference;
+pub mod config;
+pub mod error;
+pub mod git_entity;
+pub mod logging;
+pub mod output;
+pub mod picker;
+pub mod provider;
+pub mod server;
+#[cfg(test)]
+mod test_support;
+
+pub use ai_prompt::AIPrompt;
+pub use command::{
+    draft::DraftCommand, explain::ExplainCommand, multi_explain::MultiExplainCommand, CommandOutput,
+};
+pub use config::cli::{Cli, Commands, ProviderType};
+pub use config::LumenConfig;
+pub use error::LumenError;
+pub use git_entity::{commit::Commit, diff::Diff, repo::Repo, GitEntity};
+pub use output::{GenerationReport, PromptPreview};
+pub use provider::{
+    Completion, GenerationParams, LumenProvider, ModelInfo, ProviderError, ProviderRequest,
+    TokenUsage,
+};
@@ -0,0 +1,67 @@
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::sync::Mutex;
+
+use tracing_subscriber::{
+    filter::LevelFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+};
+
+use crate::error::LumenError;
+
+/// Filter in `tracing` directive syntax (e.g. `debug` or `lumen=trace`), overriding `-v`
+const LOG_ENV: &str = "LUMEN_LOG";
+
+/// Level of lumen's own events for a number of `-v` flags, other crates stay quiet
+fn verbosity_directive(verbosity: u8) -> &'static str {
+    match verbosity {
+        0 => "lumen=warn",
+        1 => "lumen=info",
+        2 => "lumen=debug",
+        _ => "lumen=trace",
+    }
+}
+
+fn filter(default: &str) -> Result<EnvFilter, LumenError> {
+    let directives = std::env::var(LOG_ENV)
+        .ok()
+        .filter(|directives| !directives.trim().is_empty());
+
+    match directives {
+        Some(directives) => EnvFilter::builder()
+            .with_default_directive(LevelFilter::OFF.into())
+            .parse(&directives)
+            .map_err(|e| LumenError::InvalidConfiguration(format!("invalid {LOG_ENV}: {e}"))),
+        None => Ok(EnvFilter::new(default)),
+    }
+}
+
+/// Logs human-readable events to stderr, and JSON lines to `log_file` when given.
+/// The file gets debug events unless `LUMEN_LOG` asks for something else.
+pub fn init(verbosity: u8, log_file: Option<&Path>) -> Result<(), LumenError> {
+    let stderr = fmt::layer()
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_timer(fmt::time::uptime())
+        .with_target(false)
+        .with_filter(filter(verbosity_directive(verbosity))?);
+
+    let file = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            let layer = fmt::layer()
+                .json()
+                .with_writer(Mutex::new(file))
+                .with_current_span(true)
+                .with_filter(filter(verbosity_directive(verbosity.max(2)))?);
+            Some(layer)
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(stderr)
+        .with(file)
+        .init();
+    Ok(())
+}
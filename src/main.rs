@@ -1,61 +1,144 @@
 use clap::Parser;
-use commit_reference::CommitReference;
-use config::cli::{Cli, Commands};
-use config::LumenConfig;
-use error::LumenError;
-use git_entity::{commit::Commit, diff::Diff, GitEntity};
+use lumen::command::{self, CommandType, LumenCommand};
+use lumen::commit_reference::CommitReference;
+use lumen::config::cli::{AuthCommand, ConfigCommand, OutputFormat};
+use lumen::config::configuration::GenerationCommand;
+use lumen::config::secret::Secret;
+use lumen::{logging, output};
+use lumen::{Cli, Commands, Commit, Diff, GitEntity, LumenConfig, LumenError, LumenProvider, Repo};
 use std::io::Read;
 use std::process;
 
-mod ai_prompt;
-mod command;
-mod commit_reference;
-mod config;
-mod error;
-mod git_entity;
-mod provider;
-
 #[tokio::main]
 async fn main() {
-    if let Err(e) = run().await {
-        eprintln!("\x1b[91m\rerror:\x1b[0m {e}");
-        process::exit(1);
+    let cli = Cli::parse();
+    let format = cli.format;
+
+    if let Err(e) = run(cli).await {
+        match format {
+            OutputFormat::Json => output::print_json_error(&e),
+            OutputFormat::Text => eprintln!("\x1b[91m\rerror:\x1b[0m {e}"),
+        }
+        process::exit(e.exit_code());
     }
 }
 
-async fn run() -> Result<(), LumenError> {
-    let cli = Cli::parse();
-    let client = reqwest::Client::new();
+async fn run(cli: Cli) -> Result<(), LumenError> {
+    logging::init(cli.verbose, cli.log_file.as_deref())?;
+
+    if let Commands::Auth { action } = &cli.command {
+        return match action {
+            AuthCommand::Login { provider } => command::auth::login(*provider),
+            AuthCommand::Logout { provider } => command::auth::logout(*provider),
+        };
+    }
+
+    let printer = output::Printer::new(cli.format, cli.plain);
+
+    if let Commands::Doctor = &cli.command {
+        return command::doctor::doctor(&cli, &printer).await;
+    }
+    if let Commands::Config { action } = &cli.command {
+        return match action {
+            ConfigCommand::Show { origin } => {
+                command::config::show(&LumenConfig::build(&cli)?, *origin, &printer)
+            }
+            ConfigCommand::Init { local } => {
+                command::config::init(*local, cli.repo.as_deref()).await
+            }
+            ConfigCommand::Validate { path } => {
+                command::config::validate(&cli, path.as_deref(), &printer)
+            }
+        };
+    }
 
     let config = match LumenConfig::build(&cli) {
         Ok(config) => config,
         Err(e) => return Err(e),
     };
 
-    let provider =
-        provider::LumenProvider::new(client, config.provider, config.api_key, config.model, config.api_base_url)?;
-    let command = command::LumenCommand::new(provider);
+    // Servers answer drafts and explanations alike, each with its command's parameters
+    let command_generation = match cli.command {
+        Commands::Serve { .. } | Commands::Mcp => Some((
+            config.command_generation(&cli, GenerationCommand::Explain)?,
+            config.command_generation(&cli, GenerationCommand::Draft)?,
+        )),
+        _ => None,
+    };
+
+    let client = reqwest::Client::new();
+    let mut api_key = config.api_key.map(|key| key.resolve()).transpose()?;
+    if cli.dry_run && api_key.is_none() {
+        // Nothing is sent, the key only shows up redacted
+        api_key = Some(Secret::new(String::new()));
+    }
+    let provider = match cli.command {
+        // Listing models works without one being configured
+        Commands::Models if config.model.is_none() => {
+            LumenProvider::for_listing(client, config.provider, api_key, config.api_base_url)?
+        }
+        _ => LumenProvider::new(client, config.provider, api_key, config.model, config.api_base_url, config.generation)?,
+    };
+    let provider = match command_generation {
+        Some((explain, draft)) => provider.with_command_generation(explain, draft),
+        None => provider,
+    };
+    if let Commands::Serve { http } = &cli.command {
+        let repo = Repo::discover(cli.repo.as_deref())?;
+        let server = command::serve::Server::new(provider, config.draft, repo);
+        return command::serve::serve(server, http.as_deref()).await;
+    }
+    if let Commands::Mcp = &cli.command {
+        let repo = Repo::discover(cli.repo.as_deref())?;
+        let server = command::mcp::McpServer::new(provider, config.draft, repo);
+        return command::mcp::serve(server).await;
+    }
+    let command = LumenCommand::new(provider, printer, cli.dry_run);
 
     match cli.command {
         Commands::Explain {
-            reference,
+            mut references,
             diff,
             staged,
+            all,
+            untracked,
             query,
+            merge_diff,
         } => {
-            let git_entity = if diff {
-                GitEntity::Diff(Diff::from_working_tree(staged)?)
+            let repo = Repo::discover(cli.repo.as_deref())?;
+            if references.len() > 1 {
+                let commits = references
+                    .into_iter()
+                    .map(|reference| match reference {
+                        CommitReference::Single(sha) if sha != "-" => Commit::new(&repo, sha),
+                        _ => Err(LumenError::InvalidArguments(
+                            "only single commits can be explained together".into(),
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                command
+                    .execute(CommandType::ExplainMany { commits, query })
+                    .await?;
+                return Ok(());
+            }
+
+            let reference = references.pop();
+            let git_entity = if all {
+                GitEntity::Diff(Diff::from_working_tree_all(&repo, untracked)?)
+            } else if diff {
+                GitEntity::Diff(Diff::from_working_tree(&repo, staged)?)
             } else if let Some(CommitReference::Single(input)) = reference {
-                let sha = if input == "-" {
+                let reference = if input == "-" {
                     read_from_stdin()?
                 } else {
                     input
                 };
-                GitEntity::Commit(Commit::new(sha)?)
+                GitEntity::from_reference(&repo, reference.trim(), merge_diff)?
             } else if let Some(CommitReference::Range { from, to }) = reference {
-                GitEntity::Diff(Diff::from_commits_range(&from, &to, false)?)
+                GitEntity::Diff(Diff::from_commits_range(&repo, &from, &to, false)?)
             }  else if let Some(CommitReference::TripleDots { from, to }) = reference {
-                GitEntity::Diff(Diff::from_commits_range(&from, &to, true)?)
+                GitEntity::Diff(Diff::from_commits_range(&repo, &from, &to, true)?)
             } else {
                 return Err(LumenError::InvalidArguments(
                     "`explain` expects SHA-1 or --diff to be present".into(),
@@ -63,15 +146,29 @@ async fn run() -> Result<(), LumenError> {
             };
 
             command
-                .execute(command::CommandType::Explain { git_entity, query })
+                .execute(CommandType::Explain { git_entity, query })
                 .await?;
         }
-        Commands::List => command.execute(command::CommandType::List).await?,
+        Commands::List(mut filter) => {
+            let repo = Repo::discover(cli.repo.as_deref())?;
+            filter.path = filter.path.iter().map(|path| repo.pathspec(path)).collect();
+            command
+                .execute(CommandType::List(config.list, filter, repo))
+                .await?
+        }
         Commands::Draft { context } => {
+            let repo = Repo::discover(cli.repo.as_deref())?;
             command
-                .execute(command::CommandType::Draft(context, config.draft))
+                .execute(CommandType::Draft(context, config.draft, repo))
                 .await?
         }
+        Commands::Models => command.execute(CommandType::Models).await?,
+        Commands::Auth { .. } | Commands::Config { .. } | Commands::Doctor => {
+            unreachable!("handled before the provider is built")
+        }
+        Commands::Serve { .. } | Commands::Mcp => {
+            unreachable!("handled before running a command")
+        }
     }
 
     Ok(())
@@ -0,0 +1,620 @@
+use std::sync::OnceLock;
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::{as_24_bit_terminal_escaped, LinesWithEndings},
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const CODE_THEME: &str = "base16-ocean.dark";
+const CODE_INDENT: &str = "    ";
+/// Lines are never wrapped narrower than this, however deeply nested the block is
+const MIN_TEXT_WIDTH: usize = 20;
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn code_theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        ThemeSet::load_defaults()
+            .themes
+            .remove(CODE_THEME)
+            .unwrap_or_default()
+    })
+}
+
+/// Renders markdown to text for the terminal: wraps paragraphs to `width`, styles inline
+/// elements and highlights fenced code blocks. With `color` off only the layout is kept.
+pub struct MarkdownRenderer {
+    width: usize,
+    color: bool,
+}
+
+impl MarkdownRenderer {
+    pub fn new(width: usize, color: bool) -> Self {
+        MarkdownRenderer { width, color }
+    }
+
+    pub fn render(&self, markdown: &str) -> String {
+        let options =
+            Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+
+        let mut state = RenderState::new(self.width, self.color);
+        for event in Parser::new_ext(markdown, options) {
+            state.handle(event);
+        }
+        state.finish()
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strike: bool,
+    dim: bool,
+    fg: Option<Color>,
+}
+
+impl Style {
+    fn paint(&self, text: &str, color: bool) -> String {
+        if !color {
+            return text.to_string();
+        }
+
+        let mut style = ContentStyle::new();
+        style.foreground_color = self.fg;
+        for (enabled, attribute) in [
+            (self.bold, Attribute::Bold),
+            (self.italic, Attribute::Italic),
+            (self.underline, Attribute::Underlined),
+            (self.strike, Attribute::CrossedOut),
+            (self.dim, Attribute::Dim),
+        ] {
+            if enabled {
+                style.attributes.set(attribute);
+            }
+        }
+        style.apply(text).to_string()
+    }
+}
+
+struct Token {
+    text: String,
+    style: Style,
+    /// Whether the token directly follows the previous one without whitespace
+    glued: bool,
+    line_break: bool,
+}
+
+#[derive(Default)]
+struct TableState {
+    rows: Vec<Vec<(String, usize)>>,
+    row: Vec<(String, usize)>,
+    header_rows: usize,
+}
+
+struct ListState {
+    next_number: Option<u64>,
+}
+
+struct RenderState {
+    out: String,
+    width: usize,
+    color: bool,
+    tokens: Vec<Token>,
+    pending_space: bool,
+    styles: Vec<Style>,
+    /// Prefixes of the enclosing block quotes and list items, repeated on every line
+    prefixes: Vec<String>,
+    /// List marker replacing the innermost prefix on the next emitted line
+    pending_marker: Option<String>,
+    lists: Vec<ListState>,
+    /// Destination and index of the first token of the enclosing links
+    links: Vec<(String, usize)>,
+    code: Option<(String, String)>,
+    table: Option<TableState>,
+}
+
+impl RenderState {
+    fn new(width: usize, color: bool) -> Self {
+        RenderState {
+            out: String::new(),
+            width,
+            color,
+            tokens: Vec::new(),
+            pending_space: false,
+            styles: Vec::new(),
+            prefixes: Vec::new(),
+            pending_marker: None,
+            lists: Vec::new(),
+            links: Vec::new(),
+            code: None,
+            table: None,
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code {
+                Some((_, code)) => code.push_str(&text),
+                None => self.push_text(&text),
+            },
+            Event::Code(code) => self.push_token(
+                &code,
+                Style {
+                    fg: Some(Color::Yellow),
+                    ..self.style()
+                },
+            ),
+            Event::InlineMath(text) | Event::DisplayMath(text) => self.push_text(&text),
+            Event::Html(html) | Event::InlineHtml(html) => self.push_text(&html),
+            Event::FootnoteReference(label) => {
+                self.push_token(&format!("[^{label}]"), self.style())
+            }
+            Event::SoftBreak => self.pending_space = true,
+            Event::HardBreak => self.tokens.push(Token {
+                text: String::new(),
+                style: Style::default(),
+                glued: true,
+                line_break: true,
+            }),
+            Event::Rule => {
+                self.block_gap();
+                let rule = "─".repeat(self.text_width());
+                let rule = Style {
+                    dim: true,
+                    ..Style::default()
+                }
+                .paint(&rule, self.color);
+                self.emit_line(&rule);
+            }
+            Event::TaskListMarker(checked) => {
+                self.push_token(if checked { "[x]" } else { "[ ]" }, self.style());
+                self.pending_space = true;
+            }
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.block_gap(),
+            Tag::Heading { level, .. } => {
+                self.block_gap();
+                let style = Style {
+                    bold: true,
+                    underline: level == HeadingLevel::H1,
+                    fg: Some(Color::Blue),
+                    ..Style::default()
+                };
+                self.styles.push(style);
+                self.push_token(&"#".repeat(level as usize), style);
+                self.pending_space = true;
+            }
+            Tag::BlockQuote(_) => {
+                self.block_gap();
+                let bar = Style {
+                    dim: true,
+                    ..Style::default()
+                }
+                .paint("│ ", self.color);
+                self.prefixes.push(bar);
+                self.styles.push(Style {
+                    italic: true,
+                    ..self.style()
+                });
+            }
+            Tag::CodeBlock(kind) => {
+                self.block_gap();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((lang, String::new()));
+            }
+            Tag::List(start) => {
+                if self.lists.is_empty() {
+                    self.block_gap();
+                } else {
+                    self.flush();
+                }
+                self.lists.push(ListState { next_number: start });
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut().and_then(|l| l.next_number.as_mut()) {
+                    Some(number) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    None => "• ".to_string(),
+                };
+                self.prefixes.push(" ".repeat(marker.width()));
+                self.pending_marker = Some(marker);
+            }
+            Tag::Table(_) => {
+                self.block_gap();
+                self.table = Some(TableState::default());
+            }
+            Tag::TableHead | Tag::TableRow | Tag::TableCell => {}
+            Tag::Emphasis => self.styles.push(Style {
+                italic: true,
+                ..self.style()
+            }),
+            Tag::Strong => self.styles.push(Style {
+                bold: true,
+                ..self.style()
+            }),
+            Tag::Strikethrough => self.styles.push(Style {
+                strike: true,
+                ..self.style()
+            }),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.links.push((dest_url.to_string(), self.tokens.len()));
+                self.styles.push(Style {
+                    underline: true,
+                    fg: Some(Color::Cyan),
+                    ..self.style()
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => self.flush(),
+            TagEnd::Heading(_) => {
+                self.flush();
+                self.styles.pop();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.prefixes.pop();
+                self.styles.pop();
+            }
+            TagEnd::CodeBlock => {
+                if let Some((lang, code)) = self.code.take() {
+                    self.emit_code(&lang, &code);
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+            }
+            TagEnd::Item => {
+                self.flush();
+                self.prefixes.pop();
+                self.pending_marker = None;
+            }
+            TagEnd::TableCell => {
+                let cell = self.take_inline();
+                if let Some(table) = &mut self.table {
+                    table.row.push(cell);
+                }
+            }
+            TagEnd::TableHead | TagEnd::TableRow => {
+                if let Some(table) = &mut self.table {
+                    let row = std::mem::take(&mut table.row);
+                    table.rows.push(row);
+                    if tag == TagEnd::TableHead {
+                        table.header_rows = table.rows.len();
+                    }
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.emit_table(table);
+                }
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.styles.pop();
+            }
+            TagEnd::Link | TagEnd::Image => {
+                self.styles.pop();
+                let Some((url, first_token)) = self.links.pop() else {
+                    return;
+                };
+                let text: String = self.tokens[first_token.min(self.tokens.len())..]
+                    .iter()
+                    .map(|token| token.text.as_str())
+                    .collect();
+                // Autolinks already show their destination
+                if url != text && url.strip_prefix("mailto:") != Some(text.as_str()) {
+                    let dim = Style {
+                        dim: true,
+                        ..Style::default()
+                    };
+                    self.pending_space = true;
+                    self.push_token(&format!("({url})"), dim);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        let trimmed = self.out.trim_end().len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+        self.out
+    }
+
+    fn style(&self) -> Style {
+        self.styles.last().copied().unwrap_or_default()
+    }
+
+    fn text_width(&self) -> usize {
+        let prefix_width: usize = self.prefixes.iter().map(|p| visible_width(p)).sum();
+        self.width.saturating_sub(prefix_width).max(MIN_TEXT_WIDTH)
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let style = self.style();
+        let mut word = String::new();
+
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !word.is_empty() {
+                    self.push_token(&std::mem::take(&mut word), style);
+                }
+                self.pending_space = true;
+            } else if c.width().unwrap_or(0) > 1 {
+                // Wide (CJK) characters can be broken anywhere, so each is its own token
+                if !word.is_empty() {
+                    self.push_token(&std::mem::take(&mut word), style);
+                }
+                self.push_token(&c.to_string(), style);
+            } else {
+                word.push(c);
+            }
+        }
+
+        if !word.is_empty() {
+            self.push_token(&word, style);
+        }
+    }
+
+    fn push_token(&mut self, text: &str, style: Style) {
+        self.tokens.push(Token {
+            text: text.to_string(),
+            style,
+            glued: !self.pending_space,
+            line_break: false,
+        });
+        self.pending_space = false;
+    }
+
+    /// Joins the pending inline tokens into a single unwrapped line with its width
+    fn take_inline(&mut self) -> (String, usize) {
+        let mut line = String::new();
+        let mut width = 0;
+        for token in std::mem::take(&mut self.tokens) {
+            if !token.glued && width > 0 {
+                line.push(' ');
+                width += 1;
+            }
+            line.push_str(&token.style.paint(&token.text, self.color));
+            width += token.text.width();
+        }
+        self.pending_space = false;
+        (line, width)
+    }
+
+    /// Wraps the pending inline tokens to the available width and emits them
+    fn flush(&mut self) {
+        if self.tokens.is_empty() {
+            return;
+        }
+
+        let available = self.text_width();
+        let mut line = String::new();
+        let mut width = 0;
+
+        for token in std::mem::take(&mut self.tokens) {
+            if token.line_break {
+                self.emit_line(&std::mem::take(&mut line));
+                width = 0;
+                continue;
+            }
+
+            let token_width = token.text.width();
+            let mut space = !token.glued && width > 0;
+            if width > 0 && width + usize::from(space) + token_width > available {
+                self.emit_line(&std::mem::take(&mut line));
+                width = 0;
+                space = false;
+            }
+            if space {
+                line.push(' ');
+                width += 1;
+            }
+            line.push_str(&token.style.paint(&token.text, self.color));
+            width += token_width;
+        }
+
+        if width > 0 {
+            self.emit_line(&line);
+        }
+        self.pending_space = false;
+    }
+
+    fn emit_line(&mut self, line: &str) {
+        let mut prefix: String = self.prefixes.concat();
+        if let Some(marker) = self.pending_marker.take() {
+            let inner = self.prefixes.last().map_or(0, String::len);
+            prefix.truncate(prefix.len() - inner);
+            prefix.push_str(&marker);
+        }
+
+        self.out.push_str(&prefix);
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    /// Separates blocks with an empty line, except inside tight list items
+    fn block_gap(&mut self) {
+        self.flush();
+        if self.out.is_empty() || self.out.ends_with("\n\n") || self.pending_marker.is_some() {
+            return;
+        }
+        let prefix = self.prefixes.concat();
+        self.out.push_str(prefix.trim_end());
+        self.out.push('\n');
+    }
+
+    fn emit_code(&mut self, lang: &str, code: &str) {
+        let syntax = syntax_set()
+            .find_syntax_by_token(lang)
+            .unwrap_or_else(|| syntax_set().find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, code_theme());
+
+        for line in LinesWithEndings::from(code) {
+            let text = line.trim_end_matches(['\n', '\r']);
+            let rendered = match highlighter.highlight_line(line, syntax_set()) {
+                Ok(ranges) if self.color => {
+                    let escaped = as_24_bit_terminal_escaped(&ranges, false);
+                    format!("{}\x1b[0m", escaped.trim_end_matches(['\n', '\r']))
+                }
+                _ => text.to_string(),
+            };
+            self.emit_line(&format!("{CODE_INDENT}{rendered}"));
+        }
+    }
+
+    fn emit_table(&mut self, table: TableState) {
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                table
+                    .rows
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|(_, width)| *width)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let separator = Style {
+            dim: true,
+            ..Style::default()
+        };
+        for (index, row) in table.rows.iter().enumerate() {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(column, width)| {
+                    let (text, text_width) = row.get(column).cloned().unwrap_or_default();
+                    format!("{text}{}", " ".repeat(width - text_width))
+                })
+                .collect();
+            let line = cells.join(&separator.paint(" │ ", self.color));
+            self.emit_line(line.trim_end());
+
+            if index + 1 == table.header_rows {
+                let rule = widths
+                    .iter()
+                    .map(|width| "─".repeat(*width))
+                    .collect::<Vec<_>>()
+                    .join("─┼─");
+                let rule = separator.paint(&rule, self.color);
+                self.emit_line(&rule);
+            }
+        }
+    }
+}
+
+/// Width of `s` on screen, ignoring ANSI escape sequences
+fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in s.chars() {
+        match c {
+            '\x1b' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if in_escape => {}
+            _ => width += c.width().unwrap_or(0),
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(markdown: &str, width: usize) -> String {
+        MarkdownRenderer::new(width, false).render(markdown)
+    }
+
+    #[test]
+    fn test_heading_and_paragraph() {
+        assert_eq!(
+            render("# Title\n\nSome *text* here.", 80),
+            "# Title\n\nSome text here.\n"
+        );
+    }
+
+    #[test]
+    fn test_wraps_paragraphs() {
+        assert_eq!(
+            render("one two three four five six seven", 20),
+            "one two three four\nfive six seven\n"
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(
+            render("1. first\n2. second\n   - nested", 80),
+            "1. first\n2. second\n   • nested\n"
+        );
+    }
+
+    #[test]
+    fn test_code_block_without_color() {
+        assert_eq!(
+            render("Before\n\n```rust\nfn main() {}\n```\n\nAfter", 80),
+            "Before\n\n    fn main() {}\n\nAfter\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_punctuation_stays_attached() {
+        assert_eq!(
+            render("Use `foo`, then **bar**.", 80),
+            "Use foo, then bar.\n"
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        assert_eq!(
+            render(
+                "See [docs](https://example.com) or <https://example.com>",
+                80
+            ),
+            "See docs (https://example.com) or https://example.com\n"
+        );
+    }
+
+    #[test]
+    fn test_color_output_contains_escape_codes() {
+        let rendered = MarkdownRenderer::new(80, true).render("**bold**");
+        assert!(rendered.contains("\x1b["));
+    }
+}
@@ -0,0 +1,419 @@
+use crossterm::terminal;
+use markdown::MarkdownRenderer;
+use serde::Serialize;
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+use crate::{
+    ai_prompt::AIPrompt,
+    config::cli::OutputFormat,
+    error::LumenError,
+    git_entity::{commit::MergeDiff, diff::Diff, GitEntity},
+    provider::{Completion, LumenProvider, ProviderRequest, TokenUsage},
+};
+
+pub mod markdown;
+pub mod pager;
+mod render;
+
+/// Version of the documents printed with `--format json`, bumped on breaking changes
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptKind {
+    Explain,
+    /// Narrative over several already explained commits
+    Aggregate,
+    Draft,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EntityDetails {
+    Commit {
+        hash: String,
+        author_name: String,
+        author_email: String,
+        date: String,
+        message: String,
+        parents: Vec<String>,
+        /// Set for merge commits, how their diff was made
+        merge_diff: Option<MergeDiff>,
+    },
+    WorkingTree {
+        staged: bool,
+    },
+    /// Staged and unstaged changes together
+    WorkingTreeAll {
+        untracked: bool,
+    },
+    Range {
+        from: String,
+        to: String,
+    },
+    Stash {
+        reference: String,
+        hash: String,
+        date: String,
+        message: String,
+    },
+    Tag {
+        name: String,
+        hash: String,
+        previous: Option<String>,
+        date: String,
+        message: String,
+    },
+}
+
+impl From<&GitEntity> for EntityDetails {
+    fn from(git_entity: &GitEntity) -> Self {
+        match git_entity {
+            GitEntity::Commit(commit) => EntityDetails::Commit {
+                hash: commit.full_hash.clone(),
+                author_name: commit.author_name.clone(),
+                author_email: commit.author_email.clone(),
+                date: commit.date.clone(),
+                message: commit.message.clone(),
+                parents: commit.parents.clone(),
+                merge_diff: commit.merge_diff,
+            },
+            GitEntity::Diff(Diff::WorkingTree { staged, .. }) => {
+                EntityDetails::WorkingTree { staged: *staged }
+            }
+            GitEntity::Diff(Diff::WorkingTreeAll { untracked, .. }) => {
+                EntityDetails::WorkingTreeAll {
+                    untracked: untracked.is_some(),
+                }
+            }
+            GitEntity::Diff(Diff::CommitsRange { from, to, .. }) => EntityDetails::Range {
+                from: from.clone(),
+                to: to.clone(),
+            },
+            GitEntity::Stash(stash) => EntityDetails::Stash {
+                reference: stash.reference.clone(),
+                hash: stash.full_hash.clone(),
+                date: stash.date.clone(),
+                message: stash.message.clone(),
+            },
+            GitEntity::Tag(tag) => EntityDetails::Tag {
+                name: tag.name.clone(),
+                hash: tag.full_hash.clone(),
+                previous: tag.previous.clone(),
+                date: tag.date.clone(),
+                message: tag.message.clone(),
+            },
+        }
+    }
+}
+
+/// A single generated text together with what it was generated from
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationReport {
+    pub kind: PromptKind,
+    pub entity: Option<EntityDetails>,
+    pub query: Option<String>,
+    pub text: String,
+    pub usage: Option<TokenUsage>,
+    pub duration_ms: u128,
+}
+
+impl GenerationReport {
+    pub fn new(
+        kind: PromptKind,
+        git_entity: Option<&GitEntity>,
+        query: Option<String>,
+        completion: Completion,
+        duration: Duration,
+    ) -> Self {
+        GenerationReport {
+            kind,
+            entity: git_entity.map(EntityDetails::from),
+            query,
+            text: completion.text,
+            usage: completion.usage,
+            duration_ms: duration.as_millis(),
+        }
+    }
+}
+
+/// A prompt and the request that would carry it, shown by `--dry-run` instead of sending it
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptPreview {
+    pub kind: PromptKind,
+    pub entity: Option<EntityDetails>,
+    pub query: Option<String>,
+    pub system_prompt: String,
+    pub user_prompt: String,
+    pub estimated_tokens: usize,
+    pub request: ProviderRequest,
+}
+
+impl PromptPreview {
+    pub fn new(
+        kind: PromptKind,
+        git_entity: Option<&GitEntity>,
+        query: Option<String>,
+        prompt: AIPrompt,
+        provider: &LumenProvider,
+    ) -> Self {
+        PromptPreview {
+            kind,
+            entity: git_entity.map(EntityDetails::from),
+            query,
+            estimated_tokens: prompt.estimated_tokens(),
+            request: provider.preview(&prompt),
+            system_prompt: prompt.system_prompt,
+            user_prompt: prompt.user_prompt,
+        }
+    }
+
+    fn title(&self) -> String {
+        let kind = match self.kind {
+            PromptKind::Explain => "explain",
+            PromptKind::Aggregate => "aggregate summary",
+            PromptKind::Draft => "draft",
+        };
+        match &self.entity {
+            Some(EntityDetails::Commit { hash, .. }) => format!("{kind} {hash}"),
+            Some(EntityDetails::WorkingTree { staged: true }) => format!("{kind} staged changes"),
+            Some(EntityDetails::WorkingTree { staged: false }) => format!("{kind} working tree"),
+            Some(EntityDetails::WorkingTreeAll { .. }) => format!("{kind} all changes"),
+            Some(EntityDetails::Range { from, to }) => format!("{kind} {from}..{to}"),
+            Some(EntityDetails::Stash { reference, .. }) => format!("{kind} {reference}"),
+            Some(EntityDetails::Tag {
+                name,
+                previous: Some(previous),
+                ..
+            }) => format!("{kind} {previous}..{name}"),
+            Some(EntityDetails::Tag { name, .. }) => format!("{kind} {name}"),
+            None => kind.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonDocument<T> {
+    version: u32,
+    status: &'static str,
+    #[serde(flatten)]
+    body: T,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    provider: &'static str,
+    model: &'a str,
+    results: &'a [GenerationReport],
+}
+
+#[derive(Serialize)]
+struct JsonDryRun<'a> {
+    dry_run: bool,
+    provider: &'static str,
+    model: &'a str,
+    prompts: &'a [PromptPreview],
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    version: u32,
+    status: &'static str,
+    error: ErrorDetails,
+}
+
+#[derive(Serialize)]
+struct ErrorDetails {
+    kind: &'static str,
+    message: String,
+    exit_code: i32,
+}
+
+/// Writes command output to stdout in the format selected on the command line
+#[derive(Debug, Clone, Copy)]
+pub struct Printer {
+    format: OutputFormat,
+    plain: bool,
+}
+
+impl Printer {
+    pub fn new(format: OutputFormat, plain: bool) -> Self {
+        Printer { format, plain }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Whether output is styled: stdout is a terminal, without `--plain` or `NO_COLOR`
+    pub fn color(&self) -> bool {
+        self.styled() && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+    }
+
+    /// `text` in the ANSI colour `code`, eg: 91 for red, or as-is when `color` is off
+    pub fn paint(&self, text: &str, code: u8) -> String {
+        if self.color() {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn styled(&self) -> bool {
+        !self.plain && io::stdout().is_terminal()
+    }
+
+    /// Renders markdown for the terminal, or prints it as-is with `--plain` or when stdout
+    /// is not a terminal. Long output is paged.
+    pub fn markdown(&self, content: &str) -> Result<(), LumenError> {
+        if !self.styled() {
+            return pager::print_paged(content);
+        }
+
+        let width = terminal::size().map_or(80, |(width, _)| width as usize);
+        let rendered = MarkdownRenderer::new(width, self.color()).render(content);
+        pager::print_paged(&rendered)
+    }
+
+    pub fn json(
+        &self,
+        provider: &LumenProvider,
+        results: &[GenerationReport],
+    ) -> Result<(), LumenError> {
+        self.json_document(&JsonReport {
+            provider: provider.name(),
+            model: provider.model(),
+            results,
+        })
+    }
+
+    /// Prints the prompts and requests a command would send, without sending them
+    pub fn dry_run(
+        &self,
+        provider: &LumenProvider,
+        prompts: &[PromptPreview],
+    ) -> Result<(), LumenError> {
+        if self.format == OutputFormat::Json {
+            return self.json_document(&JsonDryRun {
+                dry_run: true,
+                provider: provider.name(),
+                model: provider.model(),
+                prompts,
+            });
+        }
+
+        let mut output = format!(
+            "provider: {}\nmodel: {}\n",
+            provider.name(),
+            provider.model()
+        );
+        if prompts.is_empty() {
+            output.push_str("\nThis command sends no prompt.\n");
+        }
+
+        for (index, preview) in prompts.iter().enumerate() {
+            let request = &preview.request;
+            output.push_str(&format!(
+                "\n=== [{}/{}] {} (~{} tokens) ===\n",
+                index + 1,
+                prompts.len(),
+                preview.title(),
+                preview.estimated_tokens
+            ));
+            output.push_str(&format!(
+                "\n--- system ---\n{}\n\n--- user ---\n{}\n",
+                preview.system_prompt.trim_end(),
+                preview.user_prompt.trim_end()
+            ));
+
+            output.push_str(&format!(
+                "\n--- request ---\n{} {}",
+                request.method, request.url
+            ));
+            let query: Vec<String> = request
+                .query
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            if !query.is_empty() {
+                output.push_str(&format!("?{}", query.join("&")));
+            }
+            output.push('\n');
+            for (name, value) in &request.headers {
+                output.push_str(&format!("{name}: {value}\n"));
+            }
+            output.push_str(&format!(
+                "\n{}\n",
+                serde_json::to_string_pretty(&request.payload)?
+            ));
+        }
+
+        pager::print_paged(&output)
+    }
+
+    /// Prints a successful JSON document, `body` fields are flattened next to the version
+    pub fn json_document<T: Serialize>(&self, body: &T) -> Result<(), LumenError> {
+        let document = JsonDocument {
+            version: JSON_SCHEMA_VERSION,
+            status: "ok",
+            body,
+        };
+
+        println!("{}", serde_json::to_string_pretty(&document)?);
+        Ok(())
+    }
+}
+
+pub fn print_json_error(error: &LumenError) {
+    let report = JsonError {
+        version: JSON_SCHEMA_VERSION,
+        status: "error",
+        error: ErrorDetails {
+            kind: error.kind(),
+            message: error.to_string(),
+            exit_code: error.exit_code(),
+        },
+    };
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{json}"),
+        Err(_) => eprintln!("{error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_generation_report_schema() {
+        let git_entity = GitEntity::Diff(Diff::WorkingTree {
+            staged: true,
+            diff: "diff --git a/x b/x".into(),
+        });
+        let report = GenerationReport::new(
+            PromptKind::Draft,
+            Some(&git_entity),
+            None,
+            Completion {
+                text: "feat: add x".into(),
+                usage: Some(TokenUsage::new(10, 5)),
+            },
+            Duration::from_millis(42),
+        );
+
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            json!({
+                "kind": "draft",
+                "entity": { "type": "working_tree", "staged": true },
+                "query": null,
+                "text": "feat: add x",
+                "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 },
+                "duration_ms": 42
+            })
+        );
+    }
+}
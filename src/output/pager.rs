@@ -0,0 +1,68 @@
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use crossterm::terminal;
+
+use crate::error::LumenError;
+
+const DEFAULT_PAGER: &str = "less";
+
+/// `$PAGER`, or `less` when it is unset
+pub fn pager_command() -> String {
+    std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string())
+}
+
+/// Prints `content` to stdout, going through `$PAGER` when stdout is a terminal and the
+/// content doesn't fit on the screen. Falls back to printing directly if the pager can't run.
+pub fn print_paged(content: &str) -> Result<(), LumenError> {
+    let fits_screen = terminal::size()
+        .map(|(_, height)| content.lines().count() < height as usize)
+        .unwrap_or(true);
+
+    if !io::stdout().is_terminal() || fits_screen {
+        return print_direct(content);
+    }
+
+    let pager = pager_command();
+    if pager.trim() == "cat" {
+        return print_direct(content);
+    }
+
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(&pager)
+        // Keep colors, and quit right away if the content fits after all
+        .env(
+            "LESS",
+            std::env::var("LESS").unwrap_or_else(|_| "FRX".into()),
+        )
+        .stdin(Stdio::piped())
+        .spawn();
+
+    let Ok(mut child) = child else {
+        return print_direct(content);
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(content.as_bytes()) {
+            // The user quit the pager before reading everything
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+fn print_direct(content: &str) -> Result<(), LumenError> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(content.as_bytes())?;
+    if !content.ends_with('\n') {
+        stdout.write_all(b"\n")?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
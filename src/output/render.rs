@@ -0,0 +1,173 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::{
+    command::CommandOutput,
+    config::cli::OutputFormat,
+    error::LumenError,
+    git_entity::{commit::Commit, GitEntity},
+    provider::{LumenProvider, ModelInfo},
+};
+
+use super::{GenerationReport, Printer};
+
+#[derive(Serialize)]
+struct ModelEntry<'a> {
+    #[serde(flatten)]
+    model: &'a ModelInfo,
+    current: bool,
+}
+
+#[derive(Serialize)]
+struct ModelsDocument<'a> {
+    provider: &'static str,
+    model: &'a str,
+    models: Vec<ModelEntry<'a>>,
+}
+
+impl Printer {
+    /// Prints what a command produced, as text for the terminal or as a JSON document
+    pub fn render(
+        &self,
+        provider: &LumenProvider,
+        output: &CommandOutput,
+    ) -> Result<(), LumenError> {
+        match self.format() {
+            OutputFormat::Json => self.render_json(provider, output),
+            OutputFormat::Text => self.render_text(provider, output),
+        }
+    }
+
+    fn render_json(
+        &self,
+        provider: &LumenProvider,
+        output: &CommandOutput,
+    ) -> Result<(), LumenError> {
+        match output {
+            CommandOutput::Draft(report) | CommandOutput::Explanation { report, .. } => {
+                self.json(provider, std::slice::from_ref(report))
+            }
+            CommandOutput::MultiExplanation {
+                explanations,
+                summary,
+            } => {
+                let mut reports: Vec<GenerationReport> = explanations
+                    .iter()
+                    .map(|(_, report)| report.clone())
+                    .collect();
+                reports.push(summary.clone());
+                self.json(provider, &reports)
+            }
+            CommandOutput::Models { current, models } => self.json_document(&ModelsDocument {
+                provider: provider.name(),
+                model: current,
+                models: models
+                    .iter()
+                    .map(|model| ModelEntry {
+                        model,
+                        current: model.id == *current,
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn render_text(
+        &self,
+        provider: &LumenProvider,
+        output: &CommandOutput,
+    ) -> Result<(), LumenError> {
+        match output {
+            CommandOutput::Draft(report) => {
+                print!("{}", report.text);
+                std::io::stdout().flush()?;
+                Ok(())
+            }
+            CommandOutput::Explanation { git_entity, report } => {
+                self.markdown(&explanation_document(git_entity, report))
+            }
+            CommandOutput::MultiExplanation {
+                explanations,
+                summary,
+            } => self.markdown(&multi_explanation_document(explanations, summary)),
+            CommandOutput::Models { current, models } => {
+                print!("{}", models_listing(current, models));
+                if !current.is_empty() && !models.iter().any(|model| model.id == *current) {
+                    eprintln!(
+                        "warning: the configured model `{current}` is not offered by {}",
+                        provider.name()
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn explanation_document(git_entity: &GitEntity, report: &GenerationReport) -> String {
+    let mut document = git_entity.format_static_details();
+    if let Some(query) = &report.query {
+        document.push_str(&format!("\n\n`query`: {query}"));
+    }
+    document.push_str(&format!("\n\n{}", report.text));
+    document
+}
+
+fn multi_explanation_document(
+    explanations: &[(Commit, GenerationReport)],
+    summary: &GenerationReport,
+) -> String {
+    let mut document = String::new();
+    for (commit, report) in explanations {
+        document.push_str(&GitEntity::Commit(commit.clone()).format_static_details());
+        document.push_str(&format!("\n\n{}\n\n", report.text));
+    }
+
+    document.push_str("# Aggregate Summary");
+    if let Some(query) = &summary.query {
+        document.push_str(&format!("\n\n`query`: {query}"));
+    }
+    document.push_str(&format!("\n\n{}", summary.text));
+    document
+}
+
+/// One model per line, the configured one marked with `*`
+fn models_listing(current: &str, models: &[ModelInfo]) -> String {
+    let width = models.iter().map(|model| model.id.len()).max().unwrap_or(0);
+    let mut listing = String::new();
+    for model in models {
+        let marker = if model.id == current { "*" } else { " " };
+        match model.context_length {
+            Some(tokens) => {
+                listing.push_str(&format!("{marker} {:width$}  {tokens} tokens\n", model.id))
+            }
+            None => listing.push_str(&format!("{marker} {}\n", model.id)),
+        }
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_models_listing() {
+        let models = vec![
+            ModelInfo {
+                id: "small".into(),
+                context_length: Some(8000),
+            },
+            ModelInfo {
+                id: "larger".into(),
+                context_length: None,
+            },
+        ];
+
+        assert_eq!(
+            models_listing("larger", &models),
+            "  small   8000 tokens\n* larger\n"
+        );
+    }
+}
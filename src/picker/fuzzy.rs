@@ -0,0 +1,126 @@
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_BOUNDARY: i64 = 6;
+const PENALTY_GAP: i64 = 3;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Char indices into the matched text, sorted and deduplicated
+    pub positions: Vec<usize>,
+}
+
+/// Matches every whitespace-separated term of `query` against `text` as a case-insensitive
+/// subsequence, fzf-style. Returns `None` if any term does not match.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    // One entry per char of `text`, so positions index its chars even where lowercasing
+    // changes the length, eg: `İ`
+    let haystack: Vec<char> = text.chars().collect();
+
+    let mut score = 0;
+    let mut positions = Vec::new();
+    for term in query.split_whitespace() {
+        let needle: Vec<char> = term.chars().collect();
+        let (term_score, term_positions) = match_term(&needle, &haystack)?;
+        score += term_score;
+        positions.extend(term_positions);
+    }
+
+    positions.sort_unstable();
+    positions.dedup();
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Tries every possible start position for the first needle char and keeps the best scoring
+/// greedy match.
+fn match_term(needle: &[char], haystack: &[char]) -> Option<(i64, Vec<usize>)> {
+    let first = *needle.first()?;
+
+    haystack
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| same_char(**c, first))
+        .filter_map(|(start, _)| greedy_from(needle, haystack, start))
+        .max_by_key(|(score, _)| *score)
+}
+
+fn greedy_from(needle: &[char], haystack: &[char], start: usize) -> Option<(i64, Vec<usize>)> {
+    let mut positions = Vec::with_capacity(needle.len());
+    let mut score = 0;
+    let mut cursor = start;
+
+    for &c in needle {
+        let offset = haystack[cursor..].iter().position(|h| same_char(*h, c))?;
+        let index = cursor + offset;
+
+        score += SCORE_MATCH;
+        if positions.last().is_some_and(|last| last + 1 == index) {
+            score += BONUS_CONSECUTIVE;
+        } else if let Some(last) = positions.last() {
+            score -= PENALTY_GAP * (index - last - 1).min(8) as i64;
+        }
+        if index == 0 || !haystack[index - 1].is_alphanumeric() {
+            score += BONUS_BOUNDARY;
+        }
+
+        positions.push(index);
+        cursor = index + 1;
+    }
+
+    Some((score, positions))
+}
+
+fn same_char(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_query_matches_everything() {
+        assert_eq!(
+            fuzzy_match("", "anything"),
+            Some(FuzzyMatch {
+                score: 0,
+                positions: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn test_subsequence_match() {
+        let m = fuzzy_match("fxb", "fix the bug").unwrap();
+        assert_eq!(m.positions, vec![0, 2, 8]);
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert!(fuzzy_match("README", "update readme").is_some());
+    }
+
+    #[test]
+    fn test_positions_follow_chars_lowercasing_to_several() {
+        let m = fuzzy_match("fix", "İstanbul fix").unwrap();
+        assert_eq!(m.positions, vec![9, 10, 11]);
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert!(fuzzy_match("xyz", "fix the bug").is_none());
+    }
+
+    #[test]
+    fn test_all_terms_must_match() {
+        assert!(fuzzy_match("fix alice", "a1b2c3 fix parser alice 2024-01-01").is_some());
+        assert!(fuzzy_match("fix bob", "a1b2c3 fix parser alice 2024-01-01").is_none());
+    }
+
+    #[test]
+    fn test_prefers_consecutive_and_boundary_matches() {
+        let tight = fuzzy_match("parse", "fix parser").unwrap();
+        let loose = fuzzy_match("parse", "pa r s e").unwrap();
+        assert!(tight.score > loose.score);
+    }
+}
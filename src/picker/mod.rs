@@ -0,0 +1,427 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use fuzzy::{fuzzy_match, FuzzyMatch};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    config::cli::LogFilter,
+    error::LumenError,
+    git_entity::repo::{self, Repo},
+};
+
+pub mod fuzzy;
+
+/// Minimum terminal height at which the preview pane is shown below the list
+const PREVIEW_MIN_HEIGHT: u16 = 16;
+
+#[derive(Debug, Clone)]
+pub struct PickerEntry {
+    pub hash: String,
+    pub subject: String,
+    pub author: String,
+    pub date: String,
+}
+
+impl PickerEntry {
+    /// Loads the commits matching `filter`, newest first
+    pub fn load(repo: &Repo, filter: &LogFilter) -> Result<Vec<Self>, LumenError> {
+        let filter_args = filter.git_log_args();
+        let mut args = vec!["log", "--format=%h%x1f%s%x1f%an%x1f%ad", "--date=short"];
+        args.extend(filter_args.iter().map(String::as_str));
+        let output = repo.git(&args)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(LumenError::CommandError(stderr.trim().to_string()));
+        }
+
+        let stdout = repo::decode(output.stdout, &args);
+        let entries = stdout
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\x1f');
+                Some(PickerEntry {
+                    hash: fields.next()?.to_string(),
+                    subject: fields.next()?.to_string(),
+                    author: fields.next()?.to_string(),
+                    date: fields.next()?.to_string(),
+                })
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// The text the fuzzy query is matched against, also used for display
+    fn line(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.hash, self.subject, self.author, self.date
+        )
+    }
+
+    fn field_color(&self, index: usize) -> Color {
+        let hash_end = self.hash.chars().count();
+        let subject_end = hash_end + 1 + self.subject.chars().count();
+        let author_end = subject_end + 1 + self.author.chars().count();
+
+        match index {
+            i if i < hash_end => Color::Yellow,
+            i if i < subject_end => Color::Reset,
+            i if i < author_end => Color::Cyan,
+            _ => Color::DarkGrey,
+        }
+    }
+}
+
+/// Interactive fuzzy finder over commits. Returns the abbreviated hashes of the picked
+/// commits, or an empty list if the user cancelled.
+pub fn pick(repo: &Repo, entries: Vec<PickerEntry>) -> Result<Vec<String>, LumenError> {
+    if !io::stderr().is_terminal() {
+        return Err(LumenError::CommandError(
+            "`list` requires an interactive terminal".into(),
+        ));
+    }
+    if entries.is_empty() {
+        return Err(LumenError::CommandError(
+            "no commits match the given filters".into(),
+        ));
+    }
+
+    let _guard = TerminalGuard::enter()?;
+    Picker::new(repo, entries).run()
+}
+
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self, LumenError> {
+        terminal::enable_raw_mode()?;
+        queue!(io::stderr(), EnterAlternateScreen, Hide)?;
+        io::stderr().flush()?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = queue!(io::stderr(), Show, LeaveAlternateScreen);
+        let _ = io::stderr().flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Picker<'a> {
+    repo: &'a Repo,
+    entries: Vec<PickerEntry>,
+    lines: Vec<String>,
+    query: String,
+    matches: Vec<(usize, FuzzyMatch)>,
+    cursor: usize,
+    offset: usize,
+    selected: BTreeSet<usize>,
+    previews: HashMap<usize, Vec<String>>,
+}
+
+enum Action {
+    Continue,
+    Accept,
+    Cancel,
+}
+
+impl<'a> Picker<'a> {
+    fn new(repo: &'a Repo, entries: Vec<PickerEntry>) -> Self {
+        let lines = entries.iter().map(PickerEntry::line).collect();
+        let mut picker = Picker {
+            repo,
+            entries,
+            lines,
+            query: String::new(),
+            matches: Vec::new(),
+            cursor: 0,
+            offset: 0,
+            selected: BTreeSet::new(),
+            previews: HashMap::new(),
+        };
+        picker.update_matches();
+        picker
+    }
+
+    fn run(mut self) -> Result<Vec<String>, LumenError> {
+        let mut out = io::BufWriter::new(io::stderr());
+        loop {
+            self.draw(&mut out)?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match self.handle_key(key) {
+                Action::Continue => {}
+                Action::Cancel => return Ok(Vec::new()),
+                Action::Accept => return Ok(self.picked()),
+            }
+        }
+    }
+
+    fn picked(&self) -> Vec<String> {
+        if self.selected.is_empty() {
+            return self
+                .matches
+                .get(self.cursor)
+                .map(|(index, _)| vec![self.entries[*index].hash.clone()])
+                .unwrap_or_default();
+        }
+
+        self.selected
+            .iter()
+            .map(|index| self.entries[*index].hash.clone())
+            .collect()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Action::Cancel,
+            KeyCode::Char('c' | 'g') if ctrl => return Action::Cancel,
+            KeyCode::Enter => return Action::Accept,
+            KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Char('p' | 'k') if ctrl => self.move_cursor(-1),
+            KeyCode::Down => self.move_cursor(1),
+            KeyCode::Char('n' | 'j') if ctrl => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-(self.list_height() as isize)),
+            KeyCode::PageDown => self.move_cursor(self.list_height() as isize),
+            KeyCode::Tab => {
+                self.toggle_selection();
+                self.move_cursor(1);
+            }
+            KeyCode::BackTab => {
+                self.toggle_selection();
+                self.move_cursor(-1);
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update_matches();
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.update_matches();
+            }
+            KeyCode::Char('w') if ctrl => {
+                let trimmed = self.query.trim_end();
+                let cut = trimmed.rfind(' ').map_or(0, |i| i + 1);
+                self.query.truncate(cut);
+                self.update_matches();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.update_matches();
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn update_matches(&mut self) {
+        self.matches = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| fuzzy_match(&self.query, line).map(|m| (index, m)))
+            .collect();
+        // Stable sort keeps the log order among equally scored commits
+        self.matches
+            .sort_by_key(|(_, m)| std::cmp::Reverse(m.score));
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = self.matches.len() as isize - 1;
+        self.cursor = (self.cursor as isize + delta).clamp(0, last) as usize;
+    }
+
+    fn toggle_selection(&mut self) {
+        if let Some((index, _)) = self.matches.get(self.cursor) {
+            if !self.selected.remove(index) {
+                self.selected.insert(*index);
+            }
+        }
+    }
+
+    fn list_height(&self) -> usize {
+        let (_, height) = terminal::size().unwrap_or((80, 24));
+        if height >= PREVIEW_MIN_HEIGHT {
+            (height as usize - 2) / 2
+        } else {
+            (height as usize).saturating_sub(2).max(1)
+        }
+    }
+
+    fn preview(&mut self, index: usize) -> &[String] {
+        let hash = &self.entries[index].hash;
+        let repo = self.repo;
+        self.previews.entry(index).or_insert_with(|| {
+            repo.git(&["show", "--stat", "--no-color", "--format=medium", hash])
+                .map(|output| {
+                    String::from_utf8_lossy(&output.stdout)
+                        .lines()
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<(), LumenError> {
+        let (width, height) = terminal::size()?;
+        let width = width as usize;
+        let list_height = self.list_height();
+
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + list_height {
+            self.offset = self.cursor + 1 - list_height;
+        }
+
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        queue!(
+            out,
+            SetForegroundColor(Color::Blue),
+            Print("> "),
+            ResetColor,
+            Print(truncate(&self.query, width.saturating_sub(2)))
+        )?;
+
+        for row in 0..list_height {
+            let Some((index, matched)) = self.matches.get(self.offset + row) else {
+                break;
+            };
+            let is_cursor = self.offset + row == self.cursor;
+            let is_selected = self.selected.contains(index);
+
+            queue!(out, MoveTo(0, row as u16 + 1))?;
+            if is_cursor {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(
+                out,
+                SetForegroundColor(Color::Magenta),
+                Print(if is_selected { "● " } else { "  " })
+            )?;
+
+            let entry = &self.entries[*index];
+            let mut columns = 0;
+            for (i, c) in self.lines[*index].chars().enumerate() {
+                columns += c.width().unwrap_or(0);
+                if columns > width.saturating_sub(2) {
+                    break;
+                }
+                if matched.positions.binary_search(&i).is_ok() {
+                    queue!(
+                        out,
+                        SetForegroundColor(Color::Green),
+                        SetAttribute(Attribute::Bold),
+                        Print(c),
+                        SetAttribute(Attribute::NormalIntensity)
+                    )?;
+                } else {
+                    queue!(out, SetForegroundColor(entry.field_color(i)), Print(c))?;
+                }
+            }
+            queue!(out, SetAttribute(Attribute::Reset), ResetColor)?;
+        }
+
+        let status = format!(
+            " {}/{} ({} selected) ",
+            self.matches.len(),
+            self.entries.len(),
+            self.selected.len()
+        );
+        queue!(
+            out,
+            MoveTo(0, list_height as u16 + 1),
+            SetForegroundColor(Color::DarkGrey),
+            Print(truncate(
+                &format!("{status}{}", "─".repeat(width.saturating_sub(status.len()))),
+                width
+            )),
+            ResetColor
+        )?;
+
+        if height >= PREVIEW_MIN_HEIGHT {
+            if let Some(index) = self.matches.get(self.cursor).map(|(index, _)| *index) {
+                let first_row = list_height + 2;
+                let rows = (height as usize).saturating_sub(first_row);
+                let preview: Vec<String> = self.preview(index).iter().take(rows).cloned().collect();
+                for (row, line) in preview.iter().enumerate() {
+                    queue!(
+                        out,
+                        MoveTo(0, (first_row + row) as u16),
+                        Print(truncate(line, width))
+                    )?;
+                }
+            }
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// The start of `s` that fits in `width` terminal columns, wide characters such as CJK
+/// or emoji take two
+fn truncate(s: &str, width: usize) -> String {
+    let mut columns = 0;
+    s.chars()
+        .take_while(|c| {
+            columns += c.width().unwrap_or(0);
+            columns <= width
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    #[test]
+    fn test_truncate_counts_columns() {
+        assert_eq!(truncate("fix: typo", 4), "fix:");
+        assert_eq!(truncate("修复缓存", 5), "修复");
+        assert_eq!(truncate("🎉 release", 3), "🎉 ");
+        assert_eq!(truncate("short", 80), "short");
+    }
+
+    #[test]
+    fn test_load_path_relative_to_subdirectory() {
+        let temp = TempRepo::new();
+        temp.write("src/lib.rs", "pub fn hello() {}\n");
+        temp.git(&["add", "src/lib.rs"]);
+        temp.git(&["commit", "-q", "-m", "Add lib"]);
+
+        let repo = Repo::discover(Some(&temp.path().join("src"))).unwrap();
+        let filter = LogFilter {
+            path: vec![repo.pathspec("lib.rs")],
+            ..LogFilter::default()
+        };
+        let entries = PickerEntry::load(&repo, &filter).unwrap();
+        let subjects: Vec<&str> = entries.iter().map(|entry| entry.subject.as_str()).collect();
+        assert_eq!(subjects, ["Add lib"]);
+    }
+}
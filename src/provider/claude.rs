@@ -1,22 +1,27 @@
-use super::{AIProvider, ProviderError};
+use super::{
+    get_json, parse_model_list, AIProvider, Completion, GenerationParams, ModelInfo, ProviderError,
+    ProviderRequest, TokenUsage,
+};
 use crate::ai_prompt::AIPrompt;
+use crate::config::secret::Secret;
 use async_trait::async_trait;
 use reqwest::StatusCode;
 use serde_json::{json, Value};
 
 #[derive(Clone)]
 pub struct ClaudeConfig {
-    api_key: String,
+    api_key: Secret,
     model: String,
     api_base_url: String,
 }
 
 impl ClaudeConfig {
-    pub fn new(api_key: String, model: Option<String>) -> Self {
+    pub fn new(api_key: Secret, model: Option<String>, api_base_url: Option<String>) -> Self {
         Self {
             api_key,
             model: model.unwrap_or_else(|| "claude-3-5-sonnet-20241022".to_string()),
-            api_base_url: "https://api.anthropic.com/v1/messages".to_string(),
+            api_base_url: api_base_url
+                .unwrap_or_else(|| "https://api.anthropic.com/v1/messages".to_string()),
         }
     }
 }
@@ -31,28 +36,12 @@ impl ClaudeProvider {
         Self { client, config }
     }
 
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        let payload = json!({
-            "model": self.config.model,
-            "max_tokens": 4096,
-            "system": prompt.system_prompt,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": prompt.user_prompt
-                }
-            ]
-        });
-
-        let response = self
-            .client
-            .post(&self.config.api_base_url)
-            .header("x-api-key", &self.config.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        let response = self.request(&prompt, params).send(&self.client).await?;
 
         let status = response.status();
         match status {
@@ -64,7 +53,16 @@ impl ClaudeProvider {
                     .and_then(|message| message.get("text"))
                     .and_then(|text| text.as_str())
                     .ok_or(ProviderError::NoCompletionChoice)?;
-                Ok(content.to_string())
+                let usage = response_json.get("usage").and_then(|usage| {
+                    Some(TokenUsage::new(
+                        usage.get("input_tokens")?.as_u64()?,
+                        usage.get("output_tokens")?.as_u64()?,
+                    ))
+                });
+                Ok(Completion {
+                    text: content.to_string(),
+                    usage,
+                })
             }
             _ => {
                 let error_json: Value = response.json().await?;
@@ -82,7 +80,49 @@ impl ClaudeProvider {
 
 #[async_trait]
 impl AIProvider for ClaudeProvider {
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        self.complete(prompt).await
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        self.complete(prompt, params).await
+    }
+
+    fn request(&self, prompt: &AIPrompt, params: &GenerationParams) -> ProviderRequest {
+        let mut payload = json!({
+            "model": self.config.model,
+            "max_tokens": 4096,
+            "system": prompt.system_prompt,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt.user_prompt
+                }
+            ]
+        });
+        params.apply_to(
+            &mut payload,
+            ["temperature", "max_tokens", "top_p", "stop_sequences"],
+        );
+
+        ProviderRequest::post(&self.config.api_base_url, payload)
+            .header("x-api-key", self.config.api_key.expose())
+            .header("anthropic-version", "2023-06-01")
+            .header("Content-Type", "application/json")
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let url = self.config.api_base_url.replace("/messages", "/models");
+        let request = self
+            .client
+            .get(url)
+            .query(&[("limit", "1000")])
+            .header("x-api-key", self.config.api_key.expose())
+            .header("anthropic-version", "2023-06-01");
+        parse_model_list(&get_json(request).await?)
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
     }
 }
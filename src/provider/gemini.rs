@@ -1,23 +1,29 @@
-use super::{AIProvider, ProviderError};
+use super::{
+    get_json, AIProvider, Completion, GenerationParams, ModelInfo, ProviderError, ProviderRequest,
+    TokenUsage,
+};
 use crate::ai_prompt::AIPrompt;
+use crate::config::secret::Secret;
 use async_trait::async_trait;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 
 #[derive(Clone)]
 pub struct GeminiConfig {
-    api_key: String,
+    api_key: Secret,
     model: String,
     api_url_template: String, // Template like "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent"
 }
 
 impl GeminiConfig {
-    pub fn new(api_key: String, model: Option<String>) -> Self {
+    /// `api_base_url` overrides the whole template, `{model}` in it is replaced by the model
+    pub fn new(api_key: Secret, model: Option<String>, api_base_url: Option<String>) -> Self {
         Self {
             api_key,
             model: model.unwrap_or_else(|| "gemini-1.5-flash-latest".to_string()),
             // Using v1beta as it's commonly available
-            api_url_template: "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent".to_string(),
+            api_url_template: api_base_url.unwrap_or_else(|| "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent".to_string()),
         }
     }
 
@@ -30,7 +36,8 @@ impl GeminiConfig {
 #[derive(Serialize)]
 struct GeminiRequest {
     contents: Vec<Content>,
-    // Add generationConfig here if needed
+    #[serde(rename = "generationConfig", skip_serializing_if = "Map::is_empty")]
+    generation_config: Map<String, Value>,
 }
 
 #[derive(Serialize)]
@@ -49,6 +56,15 @@ struct GeminiResponse {
     candidates: Option<Vec<Candidate>>,
     // Add promptFeedback here if needed for error checking
     error: Option<GeminiErrorDetail>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    prompt_token_count: Option<u64>,
+    candidates_token_count: Option<u64>,
 }
 
 #[derive(Deserialize)]
@@ -75,7 +91,6 @@ struct GeminiErrorDetail {
     status: Option<String>,
 }
 
-
 pub struct GeminiProvider {
     client: reqwest::Client,
     config: GeminiConfig,
@@ -86,33 +101,26 @@ impl GeminiProvider {
         Self { client, config }
     }
 
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        // Gemini's simpler API often works well combining system and user prompts
-        let combined_prompt = format!("{}\n\n{}", prompt.system_prompt, prompt.user_prompt);
-
-        let request_payload = GeminiRequest {
-            contents: vec![Content {
-                parts: vec![Part { text: combined_prompt }],
-            }],
-        };
-
-        let api_url = format!("{}?key={}", self.config.get_api_url(), self.config.api_key);
-
-        let response = self
-            .client
-            .post(&api_url)
-            .header("Content-Type", "application/json")
-            .json(&request_payload)
-            .send()
-            .await?;
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        let response = self.request(&prompt, params).send(&self.client).await?;
 
         let status = response.status();
         let response_bytes = response.bytes().await?; // Read bytes first for better error reporting
 
         // Try parsing as success response
         if status == StatusCode::OK {
-             match serde_json::from_slice::<GeminiResponse>(&response_bytes) {
-                 Ok(parsed_response) => {
+            match serde_json::from_slice::<GeminiResponse>(&response_bytes) {
+                Ok(parsed_response) => {
+                    let usage = parsed_response.usage_metadata.as_ref().and_then(|usage| {
+                        Some(TokenUsage::new(
+                            usage.prompt_token_count?,
+                            usage.candidates_token_count?,
+                        ))
+                    });
                     let text = parsed_response
                         .candidates
                         .and_then(|mut c| c.pop()) // Take the first candidate
@@ -121,14 +129,14 @@ impl GeminiProvider {
                         .and_then(|mut p| p.pop()) // Take the first part
                         .and_then(|p| p.text)
                         .ok_or(ProviderError::NoCompletionChoice)?;
-                    return Ok(text);
-                 }
-                 Err(e) => {
+                    return Ok(Completion { text, usage });
+                }
+                Err(e) => {
                     // If parsing success response fails, return unexpected response
-                    eprintln!("Failed to parse successful Gemini response: {}", e);
+                    tracing::warn!(error = %e, "failed to parse successful Gemini response");
                     return Err(ProviderError::UnexpectedResponse);
-                 }
-             }
+                }
+            }
         }
 
         // If status is not OK, try parsing as error response
@@ -136,17 +144,25 @@ impl GeminiProvider {
             Ok(error_response) => {
                 let error_message = error_response
                     .error
-                    .and_then(|e| {
-                        let code_info = e.code.map_or("".to_string(), |c| format!(" (Code: {})", c));
-                        let status_info = e.status.map_or("".to_string(), |s| format!(" Status: {}", s));
-                        Some(format!("{}{}{}", e.message.unwrap_or_default(), code_info, status_info))
+                    .map(|e| {
+                        let code_info =
+                            e.code.map_or("".to_string(), |c| format!(" (Code: {})", c));
+                        let status_info = e
+                            .status
+                            .map_or("".to_string(), |s| format!(" Status: {}", s));
+                        format!(
+                            "{}{}{}",
+                            e.message.unwrap_or_default(),
+                            code_info,
+                            status_info
+                        )
                     })
                     .unwrap_or_else(|| String::from_utf8_lossy(&response_bytes).to_string()); // Fallback to raw response
                 Err(ProviderError::APIError(status, error_message))
-            },
+            }
             Err(_) => {
                 // If parsing error response also fails, return raw response
-                 Err(ProviderError::APIError(
+                Err(ProviderError::APIError(
                     status,
                     String::from_utf8_lossy(&response_bytes).to_string(),
                 ))
@@ -157,7 +173,74 @@ impl GeminiProvider {
 
 #[async_trait]
 impl AIProvider for GeminiProvider {
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        self.complete(prompt).await
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        self.complete(prompt, params).await
     }
-} 
\ No newline at end of file
+
+    fn request(&self, prompt: &AIPrompt, params: &GenerationParams) -> ProviderRequest {
+        // Gemini's simpler API often works well combining system and user prompts
+        let combined_prompt = format!("{}\n\n{}", prompt.system_prompt, prompt.user_prompt);
+
+        let request_payload = GeminiRequest {
+            contents: vec![Content {
+                parts: vec![Part {
+                    text: combined_prompt,
+                }],
+            }],
+            generation_config: params.native_fields([
+                "temperature",
+                "maxOutputTokens",
+                "topP",
+                "stopSequences",
+            ]),
+        };
+
+        ProviderRequest::post(self.config.get_api_url(), json!(request_payload))
+            .query("key", self.config.api_key.expose())
+            .header("Content-Type", "application/json")
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let url = self.config.get_api_url();
+        let url = url
+            .split_once("/models/")
+            .map_or(url.as_str(), |(base, _)| base);
+        let request = self
+            .client
+            .get(format!("{url}/models"))
+            .query(&[("key", self.config.api_key.expose()), ("pageSize", "1000")]);
+        let response = get_json(request).await?;
+        let models = response
+            .get("models")
+            .and_then(Value::as_array)
+            .ok_or(ProviderError::UnexpectedResponse)?;
+
+        Ok(models
+            .iter()
+            .filter(|model| {
+                model
+                    .get("supportedGenerationMethods")
+                    .and_then(Value::as_array)
+                    .is_some_and(|methods| methods.iter().any(|m| m == "generateContent"))
+            })
+            .filter_map(|model| {
+                Some(ModelInfo {
+                    id: model
+                        .get("name")?
+                        .as_str()?
+                        .trim_start_matches("models/")
+                        .to_string(),
+                    context_length: model.get("inputTokenLimit").and_then(Value::as_u64),
+                })
+            })
+            .collect())
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+}
@@ -1,22 +1,27 @@
-use super::{AIProvider, ProviderError};
+use super::{
+    get_json, openai_models_url, parse_model_list, AIProvider, Completion, GenerationParams,
+    ModelInfo, ProviderError, ProviderRequest, TokenUsage,
+};
 use crate::ai_prompt::AIPrompt;
+use crate::config::secret::Secret;
 use async_trait::async_trait;
 use reqwest::StatusCode;
 use serde_json::{json, Value};
 
 #[derive(Clone)]
 pub struct GroqConfig {
-    api_key: String,
+    api_key: Secret,
     model: String,
     api_base_url: String,
 }
 
 impl GroqConfig {
-    pub fn new(api_key: String, model: Option<String>) -> Self {
+    pub fn new(api_key: Secret, model: Option<String>, api_base_url: Option<String>) -> Self {
         Self {
             api_key,
-            model: model.unwrap_or_else(|| "mixtral-8x7b-32768".to_string()),
-            api_base_url: "https://api.groq.com/openai/v1/chat/completions".to_string(),
+            model: model.unwrap_or_else(|| "llama-3.3-70b-versatile".to_string()),
+            api_base_url: api_base_url
+                .unwrap_or_else(|| "https://api.groq.com/openai/v1/chat/completions".to_string()),
         }
     }
 }
@@ -31,28 +36,12 @@ impl GroqProvider {
         Self { client, config }
     }
 
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        let payload = json!({
-            "model": self.config.model,
-            "messages": [
-                {
-                    "role": "system",
-                    "content": prompt.system_prompt
-                },
-                {
-                    "role": "user",
-                    "content": prompt.user_prompt
-                }
-            ]
-        });
-
-        let response = self
-            .client
-            .post(&self.config.api_base_url)
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .json(&payload)
-            .send()
-            .await?;
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        let response = self.request(&prompt, params).send(&self.client).await?;
 
         let status = response.status();
         match status {
@@ -65,7 +54,16 @@ impl GroqProvider {
                     .and_then(|message| message.get("content"))
                     .and_then(|content| content.as_str())
                     .ok_or(ProviderError::NoCompletionChoice)?;
-                Ok(content.to_string())
+                let usage = response_json.get("usage").and_then(|usage| {
+                    Some(TokenUsage::new(
+                        usage.get("prompt_tokens")?.as_u64()?,
+                        usage.get("completion_tokens")?.as_u64()?,
+                    ))
+                });
+                Ok(Completion {
+                    text: content.to_string(),
+                    usage,
+                })
             }
             _ => {
                 let error_json: Value = response.json().await?;
@@ -83,7 +81,45 @@ impl GroqProvider {
 
 #[async_trait]
 impl AIProvider for GroqProvider {
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        self.complete(prompt).await
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        self.complete(prompt, params).await
+    }
+
+    fn request(&self, prompt: &AIPrompt, params: &GenerationParams) -> ProviderRequest {
+        let mut payload = json!({
+            "model": self.config.model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": prompt.system_prompt
+                },
+                {
+                    "role": "user",
+                    "content": prompt.user_prompt
+                }
+            ]
+        });
+        params.apply_to(&mut payload, GenerationParams::KEYS);
+
+        ProviderRequest::post(&self.config.api_base_url, payload).header(
+            "Authorization",
+            format!("Bearer {}", self.config.api_key.expose()),
+        )
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let request = self
+            .client
+            .get(openai_models_url(&self.config.api_base_url))
+            .bearer_auth(self.config.api_key.expose());
+        parse_model_list(&get_json(request).await?)
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
     }
 }
@@ -0,0 +1,182 @@
+use super::{
+    AIProvider, Completion, GenerationParams, ModelInfo, ProviderError, ProviderRequest, TokenUsage,
+};
+use crate::ai_prompt::AIPrompt;
+use crate::config::configuration::ConfigFormat;
+use crate::error::LumenError;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::Path;
+
+/// Env variable with the path of the fixtures file, JSON, TOML or YAML
+pub const FIXTURES_ENV: &str = "LUMEN_MOCK_FIXTURES";
+
+/// Canned responses, the first one matching the prompt is returned
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixtures {
+    /// Models listed by `lumen models`, only the configured one when empty
+    #[serde(default)]
+    models: Vec<String>,
+    #[serde(default)]
+    responses: Vec<Fixture>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Fixture {
+    /// Only matches prompts containing this text, fixtures without it match any prompt
+    contains: Option<String>,
+    /// Response template, `{{model}}`, `{{system_prompt}}`, `{{user_prompt}}` and
+    /// `{{prompt_tokens}}` are replaced
+    response: String,
+}
+
+impl Fixtures {
+    pub fn load(path: &Path) -> Result<Self, LumenError> {
+        ConfigFormat::load(path)
+    }
+
+    fn find(&self, prompt: &AIPrompt) -> Option<&Fixture> {
+        self.responses.iter().find(|fixture| {
+            fixture.contains.as_deref().is_none_or(|text| {
+                prompt.system_prompt.contains(text) || prompt.user_prompt.contains(text)
+            })
+        })
+    }
+}
+
+pub struct MockConfig {
+    model: String,
+    fixtures: Fixtures,
+}
+
+impl MockConfig {
+    /// Loads the fixtures from `LUMEN_MOCK_FIXTURES`, without it every prompt gets a generic answer
+    pub fn new(model: Option<String>) -> Result<Self, LumenError> {
+        let fixtures = match std::env::var_os(FIXTURES_ENV) {
+            Some(path) => Fixtures::load(Path::new(&path))?,
+            None => Fixtures::default(),
+        };
+
+        Ok(Self {
+            model: model.unwrap_or_else(|| "mock".to_string()),
+            fixtures,
+        })
+    }
+}
+
+/// Offline provider answering from fixtures, for testing scripts built around lumen
+pub struct MockProvider {
+    config: MockConfig,
+}
+
+impl MockProvider {
+    pub fn new(config: MockConfig) -> Self {
+        Self { config }
+    }
+
+    fn render(&self, template: &str, prompt: &AIPrompt) -> String {
+        template
+            .replace("{{model}}", &self.config.model)
+            .replace("{{system_prompt}}", &prompt.system_prompt)
+            .replace("{{user_prompt}}", &prompt.user_prompt)
+            .replace("{{prompt_tokens}}", &prompt.estimated_tokens().to_string())
+    }
+}
+
+#[async_trait]
+impl AIProvider for MockProvider {
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        _params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        let fixture = self.config.fixtures.find(&prompt);
+        tracing::debug!(matched = fixture.is_some(), "mock completion");
+        let text = match fixture {
+            Some(fixture) => self.render(&fixture.response, &prompt),
+            None => format!("Mock response from {}", self.config.model),
+        };
+
+        let completion_tokens = text.chars().count().div_ceil(4) as u64;
+        Ok(Completion {
+            usage: Some(TokenUsage::new(
+                prompt.estimated_tokens() as u64,
+                completion_tokens,
+            )),
+            text,
+        })
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let models = match self.config.fixtures.models.as_slice() {
+            [] => vec![self.config.model.clone()],
+            models => models.to_vec(),
+        };
+
+        Ok(models
+            .into_iter()
+            .map(|id| ModelInfo {
+                id,
+                context_length: None,
+            })
+            .collect())
+    }
+
+    /// Nothing is sent, this shows what the fixtures are matched against
+    fn request(&self, prompt: &AIPrompt, _params: &GenerationParams) -> ProviderRequest {
+        ProviderRequest::post(
+            "mock://fixtures",
+            json!({
+                "model": self.config.model,
+                "system_prompt": prompt.system_prompt,
+                "user_prompt": prompt.user_prompt,
+            }),
+        )
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_fixtures() {
+        let fixtures: Fixtures = serde_json::from_str(
+            r#"{
+                "responses": [
+                    { "contains": "README", "response": "docs: update readme" },
+                    { "response": "{{model}} saw ~{{prompt_tokens}} tokens" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let provider = MockProvider::new(MockConfig {
+            model: "mock".to_string(),
+            fixtures,
+        });
+        let prompt = |user: &str| AIPrompt {
+            system_prompt: String::new(),
+            user_prompt: user.to_string(),
+        };
+        let params = GenerationParams::default();
+
+        let completion = provider
+            .complete(prompt("+++ b/README.md"), &params)
+            .await
+            .unwrap();
+        assert_eq!(completion.text, "docs: update readme");
+
+        let completion = provider
+            .complete(prompt("12345678"), &params)
+            .await
+            .unwrap();
+        assert_eq!(completion.text, "mock saw ~2 tokens");
+    }
+}
@@ -3,29 +3,308 @@ use async_trait::async_trait;
 use claude::{ClaudeConfig, ClaudeProvider};
 use gemini::{GeminiConfig, GeminiProvider};
 use groq::{GroqConfig, GroqProvider};
+use mock::{MockConfig, MockProvider};
 use ollama::{OllamaConfig, OllamaProvider};
 use openai::{OpenAIConfig, OpenAIProvider};
 use openrouter::{OpenRouterConfig, OpenRouterProvider};
 use phind::{PhindConfig, PhindProvider};
+use recording::Mode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::time::Instant;
 use thiserror::Error;
+use tracing::Instrument;
 
 use crate::{
     ai_prompt::{AIPrompt, AIPromptError},
-    command::{draft::DraftCommand, explain::ExplainCommand},
+    command::{draft::DraftCommand, explain::ExplainCommand, multi_explain::MultiExplainCommand},
+    config::{secret::Secret, LumenConfig},
     error::LumenError,
 };
 
 pub mod claude;
 pub mod gemini;
 pub mod groq;
+pub mod mock;
 pub mod ollama;
 pub mod openai;
 pub mod openrouter;
 pub mod phind;
+pub mod recording;
 
 #[async_trait]
 pub trait AIProvider {
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError>;
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError>;
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError>;
+
+    /// The request `complete` sends for `prompt`
+    fn request(&self, prompt: &AIPrompt, params: &GenerationParams) -> ProviderRequest;
+
+    fn model(&self) -> &str;
+}
+
+/// Header and query names whose values are credentials
+const SENSITIVE_NAMES: [&str; 3] = ["authorization", "x-api-key", "key"];
+
+/// A completion request as sent over the wire, built apart from sending so it can be previewed
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderRequest {
+    pub method: &'static str,
+    pub url: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub query: BTreeMap<String, String>,
+    pub headers: BTreeMap<String, String>,
+    pub payload: Value,
+}
+
+impl ProviderRequest {
+    pub fn post(url: impl Into<String>, payload: Value) -> Self {
+        ProviderRequest {
+            method: "POST",
+            url: url.into(),
+            query: BTreeMap::new(),
+            headers: BTreeMap::new(),
+            payload,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn query(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.query.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// The same request with credentials masked, safe to print
+    pub fn redacted(&self) -> Self {
+        let mask = |values: &BTreeMap<String, String>| {
+            values
+                .iter()
+                .map(|(name, value)| {
+                    let sensitive = SENSITIVE_NAMES.contains(&name.to_lowercase().as_str());
+                    let value = if sensitive { "********" } else { value };
+                    (name.clone(), value.to_string())
+                })
+                .collect()
+        };
+
+        ProviderRequest {
+            query: mask(&self.query),
+            headers: mask(&self.headers),
+            ..self.clone()
+        }
+    }
+
+    pub async fn send(&self, client: &reqwest::Client) -> Result<reqwest::Response, ProviderError> {
+        let mut request = client.post(&self.url).json(&self.payload);
+        if !self.query.is_empty() {
+            request = request.query(&self.query);
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        tracing::trace!(payload = %self.payload, "request payload");
+        send_logged(client, request.build()?).await
+    }
+}
+
+/// A model offered by a provider
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Context window in tokens, when the provider reports it
+    pub context_length: Option<u64>,
+}
+
+/// Sends a request, logging its method, URL without the query, status and latency
+async fn send_logged(
+    client: &reqwest::Client,
+    request: reqwest::Request,
+) -> Result<reqwest::Response, ProviderError> {
+    let method = request.method().clone();
+    // The query is left out since some providers take the API key there
+    let mut url = request.url().clone();
+    url.set_query(None);
+
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(<[u8]>::to_vec);
+
+    let started = Instant::now();
+    let response = match recording::mode() {
+        Mode::Replay(dir) => recording::replay(dir, &method, &url, body.as_deref()),
+        _ => client
+            .execute(request)
+            .await
+            .map_err(|e| e.with_url(url.clone()).into()),
+    };
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+
+    match &response {
+        Ok(response) => {
+            tracing::info!(%method, %url, status = response.status().as_u16(), elapsed_ms, "http request")
+        }
+        Err(e) => tracing::info!(%method, %url, elapsed_ms, error = %e, "http request failed"),
+    }
+
+    let response = response?;
+    if let Mode::Record(dir) = recording::mode() {
+        let status = response.status().as_u16();
+        let response_body = response.bytes().await?;
+        recording::save(dir, &method, &url, body.as_deref(), status, &response_body)?;
+        return recording::response(status, response_body);
+    }
+    Ok(response)
+}
+
+/// Sends a GET request and returns the JSON body, turning error statuses into `APIError`
+async fn get_json(request: reqwest::RequestBuilder) -> Result<Value, ProviderError> {
+    let (client, request) = request.build_split();
+    let response = send_logged(&client, request?).await?;
+    let status = response.status();
+
+    if !status.is_success() {
+        let body = response.text().await?;
+        return Err(ProviderError::APIError(status, body));
+    }
+    Ok(response.json().await?)
+}
+
+/// Model listing URL of an OpenAI compatible API, derived from its chat completions URL
+fn openai_models_url(chat_url: &str) -> String {
+    let base = chat_url
+        .strip_suffix("/chat/completions")
+        .unwrap_or(chat_url)
+        .trim_end_matches('/');
+    format!("{base}/models")
+}
+
+/// Parses `{ "data": [{ "id": ... }] }` model lists, as returned by OpenAI compatible APIs
+fn parse_model_list(response: &Value) -> Result<Vec<ModelInfo>, ProviderError> {
+    let models = response
+        .get("data")
+        .and_then(Value::as_array)
+        .ok_or(ProviderError::UnexpectedResponse)?;
+
+    Ok(models
+        .iter()
+        .filter_map(|model| {
+            Some(ModelInfo {
+                id: model.get("id")?.as_str()?.to_string(),
+                context_length: model
+                    .get("context_window")
+                    .or_else(|| model.get("context_length"))
+                    .and_then(Value::as_u64),
+            })
+        })
+        .collect())
+}
+
+pub struct Completion {
+    pub text: String,
+    /// Token counts as reported by the provider, if it reports them
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn new(prompt_tokens: u64, completion_tokens: u64) -> Self {
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+}
+
+/// Sampling settings, unset fields are left to the provider's defaults
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenerationParams {
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f64>,
+    pub stop: Option<Vec<String>>,
+}
+
+impl GenerationParams {
+    pub const KEYS: [&'static str; 4] = ["temperature", "max_tokens", "top_p", "stop"];
+
+    /// Takes the fields set in `other`, returning which ones were set in `KEYS` order
+    pub fn merge(&mut self, other: GenerationParams) -> [bool; 4] {
+        let set = [
+            other.temperature.is_some(),
+            other.max_tokens.is_some(),
+            other.top_p.is_some(),
+            other.stop.is_some(),
+        ];
+
+        self.temperature = other.temperature.or(self.temperature);
+        self.max_tokens = other.max_tokens.or(self.max_tokens);
+        self.top_p = other.top_p.or(self.top_p);
+        self.stop = other.stop.or(self.stop.take());
+        set
+    }
+
+    /// Checks the values against the ranges `provider` accepts
+    pub fn validate(&self, provider: ProviderType) -> Result<(), String> {
+        let max = provider.max_temperature();
+        if let Some(temperature) = self.temperature.filter(|t| !(0.0..=max).contains(t)) {
+            return Err(format!(
+                "temperature must be between 0 and {max} for {provider}, got {temperature}"
+            ));
+        }
+        if let Some(top_p) = self.top_p.filter(|p| !(0.0..=1.0).contains(p)) {
+            return Err(format!("top_p must be between 0 and 1, got {top_p}"));
+        }
+        if self.max_tokens == Some(0) {
+            return Err("max_tokens must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Adds the set fields to a JSON object payload under the provider's own names
+    pub fn apply_to(&self, payload: &mut Value, names: [&str; 4]) {
+        if let Value::Object(payload) = payload {
+            payload.extend(self.native_fields(names));
+        }
+    }
+
+    /// The set fields under the provider's own names, given in `KEYS` order
+    pub fn native_fields(&self, names: [&str; 4]) -> Map<String, Value> {
+        let [temperature, max_tokens, top_p, stop] = names;
+        let mut fields = Map::new();
+
+        if let Some(value) = self.temperature {
+            fields.insert(temperature.to_string(), json!(value));
+        }
+        if let Some(value) = self.max_tokens {
+            fields.insert(max_tokens.to_string(), json!(value));
+        }
+        if let Some(value) = self.top_p {
+            fields.insert(top_p.to_string(), json!(value));
+        }
+        if let Some(value) = &self.stop {
+            fields.insert(stop.to_string(), json!(value));
+        }
+        fields
+    }
 }
 
 #[derive(Error, Debug)]
@@ -44,9 +323,20 @@ pub enum ProviderError {
 
     #[error("Unexpected response")]
     UnexpectedResponse,
+
+    #[error("Recorded exchange: {0}")]
+    Recording(String),
+}
+
+/// The configured provider together with the generation parameters sent with every request
+pub struct LumenProvider {
+    backend: ProviderBackend,
+    generation: GenerationParams,
+    /// Sent with drafts instead of `generation`, set by servers answering both
+    draft_generation: Option<GenerationParams>,
 }
 
-pub enum LumenProvider {
+enum ProviderBackend {
     OpenAI(Box<OpenAIProvider>),
     Phind(Box<PhindProvider>),
     Groq(Box<GroqProvider>),
@@ -54,84 +344,330 @@ pub enum LumenProvider {
     Ollama(Box<OllamaProvider>),
     OpenRouter(Box<OpenRouterProvider>),
     Gemini(Box<GeminiProvider>),
+    Mock(Box<MockProvider>),
 }
 
 impl LumenProvider {
     pub fn new(
         client: reqwest::Client,
         provider_type: ProviderType,
-        api_key: Option<String>,
+        api_key: Option<Secret>,
         model: Option<String>,
         api_base_url: Option<String>,
+        generation: GenerationParams,
     ) -> Result<Self, LumenError> {
-        match provider_type {
+        let backend = match provider_type {
             ProviderType::Openai => {
                 let api_key = api_key.ok_or(LumenError::MissingApiKey("OpenAI".to_string()))?;
                 let config = OpenAIConfig::new(api_key, model, api_base_url);
-                let provider = LumenProvider::OpenAI(Box::new(OpenAIProvider::new(client, config)));
-                Ok(provider)
+                ProviderBackend::OpenAI(Box::new(OpenAIProvider::new(client, config)))
             }
-            ProviderType::Phind => Ok(LumenProvider::Phind(Box::new(PhindProvider::new(
+            ProviderType::Phind => ProviderBackend::Phind(Box::new(PhindProvider::new(
                 client,
-                PhindConfig::new(model),
-            )))),
+                PhindConfig::new(model, api_base_url),
+            ))),
             ProviderType::Groq => {
                 let api_key = api_key.ok_or(LumenError::MissingApiKey("Groq".to_string()))?;
-                let config = GroqConfig::new(api_key, model);
-                let provider = LumenProvider::Groq(Box::new(GroqProvider::new(client, config)));
-                Ok(provider)
+                let config = GroqConfig::new(api_key, model, api_base_url);
+                ProviderBackend::Groq(Box::new(GroqProvider::new(client, config)))
             }
             ProviderType::Claude => {
                 let api_key = api_key.ok_or(LumenError::MissingApiKey("Claude".to_string()))?;
-                let config = ClaudeConfig::new(api_key, model);
-                let provider = LumenProvider::Claude(Box::new(ClaudeProvider::new(client, config)));
-                Ok(provider)
+                let config = ClaudeConfig::new(api_key, model, api_base_url);
+                ProviderBackend::Claude(Box::new(ClaudeProvider::new(client, config)))
             }
             ProviderType::Ollama => {
                 let model = model.ok_or(LumenError::MissingModel("Ollama".to_string()))?;
-                let config = OllamaConfig::new(model);
-                let provider = LumenProvider::Ollama(Box::new(OllamaProvider::new(client, config)));
-                Ok(provider)
+                let config = OllamaConfig::new(model, api_base_url);
+                ProviderBackend::Ollama(Box::new(OllamaProvider::new(client, config)))
             }
             ProviderType::Openrouter => {
                 let api_key = api_key.ok_or(LumenError::MissingApiKey("OpenRouter".to_string()))?;
-                let config = OpenRouterConfig::new(api_key, model);
-                let provider =
-                    LumenProvider::OpenRouter(Box::new(OpenRouterProvider::new(client, config)));
-                Ok(provider)
+                let config = OpenRouterConfig::new(api_key, model, api_base_url);
+                ProviderBackend::OpenRouter(Box::new(OpenRouterProvider::new(client, config)))
             }
             ProviderType::Gemini => {
                 let api_key = api_key.ok_or(LumenError::MissingApiKey("Gemini".to_string()))?;
-                let config = GeminiConfig::new(api_key, model);
-                let provider = LumenProvider::Gemini(Box::new(GeminiProvider::new(client, config)));
-                Ok(provider)
+                let config = GeminiConfig::new(api_key, model, api_base_url);
+                ProviderBackend::Gemini(Box::new(GeminiProvider::new(client, config)))
+            }
+            ProviderType::Mock => {
+                ProviderBackend::Mock(Box::new(MockProvider::new(MockConfig::new(model)?)))
             }
+        };
+
+        Ok(LumenProvider {
+            backend,
+            generation,
+            draft_generation: None,
+        })
+    }
+
+    /// Sends `explain` with `generation` and drafts with `draft`, eg: the per-command
+    /// defaults for a server
+    pub fn with_command_generation(
+        mut self,
+        explain: GenerationParams,
+        draft: GenerationParams,
+    ) -> Self {
+        self.generation = explain;
+        self.draft_generation = Some(draft);
+        self
+    }
+
+    /// Provider for a resolved config, running `api_key_cmd` if that is where the key comes from
+    pub fn from_config(config: &LumenConfig) -> Result<Self, LumenError> {
+        let api_key = config
+            .api_key
+            .as_ref()
+            .map(|key| key.resolve())
+            .transpose()?;
+        Self::new(
+            reqwest::Client::new(),
+            config.provider,
+            api_key,
+            config.model.clone(),
+            config.api_base_url.clone(),
+            config.generation.clone(),
+        )
+    }
+
+    pub fn name(&self) -> &'static str {
+        match &self.backend {
+            ProviderBackend::OpenAI(_) => "openai",
+            ProviderBackend::Phind(_) => "phind",
+            ProviderBackend::Groq(_) => "groq",
+            ProviderBackend::Claude(_) => "claude",
+            ProviderBackend::Ollama(_) => "ollama",
+            ProviderBackend::OpenRouter(_) => "openrouter",
+            ProviderBackend::Gemini(_) => "gemini",
+            ProviderBackend::Mock(_) => "mock",
         }
     }
 
-    pub async fn explain(&self, command: &ExplainCommand) -> Result<String, ProviderError> {
-        let prompt = AIPrompt::build_explain_prompt(command)?;
-        match self {
-            LumenProvider::OpenAI(provider) => provider.complete(prompt).await,
-            LumenProvider::Phind(provider) => provider.complete(prompt).await,
-            LumenProvider::Groq(provider) => provider.complete(prompt).await,
-            LumenProvider::Claude(provider) => provider.complete(prompt).await,
-            LumenProvider::Ollama(provider) => provider.complete(prompt).await,
-            LumenProvider::OpenRouter(provider) => provider.complete(prompt).await,
-            LumenProvider::Gemini(provider) => provider.complete(prompt).await,
+    pub fn model(&self) -> &str {
+        match &self.backend {
+            ProviderBackend::OpenAI(provider) => provider.model(),
+            ProviderBackend::Phind(provider) => provider.model(),
+            ProviderBackend::Groq(provider) => provider.model(),
+            ProviderBackend::Claude(provider) => provider.model(),
+            ProviderBackend::Ollama(provider) => provider.model(),
+            ProviderBackend::OpenRouter(provider) => provider.model(),
+            ProviderBackend::Gemini(provider) => provider.model(),
+            ProviderBackend::Mock(provider) => provider.model(),
         }
     }
 
-    pub async fn draft(&self, command: &DraftCommand) -> Result<String, ProviderError> {
+    pub async fn explain(&self, command: &ExplainCommand) -> Result<Completion, ProviderError> {
+        let prompt = AIPrompt::build_explain_prompt(command)?;
+        self.complete(prompt, &self.generation).await
+    }
+
+    pub async fn explain_many(
+        &self,
+        command: &MultiExplainCommand,
+        explanations: &[String],
+    ) -> Result<Completion, ProviderError> {
+        let prompt = AIPrompt::build_multi_explain_prompt(command, explanations)?;
+        self.complete(prompt, &self.generation).await
+    }
+
+    pub async fn draft(&self, command: &DraftCommand) -> Result<Completion, ProviderError> {
         let prompt = AIPrompt::build_draft_prompt(command)?;
-        match self {
-            LumenProvider::OpenAI(provider) => provider.complete(prompt).await,
-            LumenProvider::Phind(provider) => provider.complete(prompt).await,
-            LumenProvider::Groq(provider) => provider.complete(prompt).await,
-            LumenProvider::Claude(provider) => provider.complete(prompt).await,
-            LumenProvider::Ollama(provider) => provider.complete(prompt).await,
-            LumenProvider::OpenRouter(provider) => provider.complete(prompt).await,
-            LumenProvider::Gemini(provider) => provider.complete(prompt).await,
+        let generation = self.draft_generation.as_ref().unwrap_or(&self.generation);
+        self.complete(prompt, generation).await
+    }
+
+    /// Provider without a model, enough to list the models it offers
+    pub fn for_listing(
+        client: reqwest::Client,
+        provider_type: ProviderType,
+        api_key: Option<Secret>,
+        api_base_url: Option<String>,
+    ) -> Result<Self, LumenError> {
+        let model = provider_type.requires_model().then(String::new);
+        Self::new(
+            client,
+            provider_type,
+            api_key,
+            model,
+            api_base_url,
+            GenerationParams::default(),
+        )
+    }
+
+    /// Models offered by the provider, sorted by id
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let span = tracing::info_span!("list_models", provider = self.name());
+        let mut models = async {
+            match &self.backend {
+                ProviderBackend::OpenAI(provider) => provider.list_models().await,
+                ProviderBackend::Phind(provider) => provider.list_models().await,
+                ProviderBackend::Groq(provider) => provider.list_models().await,
+                ProviderBackend::Claude(provider) => provider.list_models().await,
+                ProviderBackend::Ollama(provider) => provider.list_models().await,
+                ProviderBackend::OpenRouter(provider) => provider.list_models().await,
+                ProviderBackend::Gemini(provider) => provider.list_models().await,
+                ProviderBackend::Mock(provider) => provider.list_models().await,
+            }
+        }
+        .instrument(span)
+        .await?;
+
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(models)
+    }
+
+    /// The request that would carry `prompt`, with credentials masked
+    pub fn preview(&self, prompt: &AIPrompt) -> ProviderRequest {
+        let params = &self.generation;
+        let request = match &self.backend {
+            ProviderBackend::OpenAI(provider) => provider.request(prompt, params),
+            ProviderBackend::Phind(provider) => provider.request(prompt, params),
+            ProviderBackend::Groq(provider) => provider.request(prompt, params),
+            ProviderBackend::Claude(provider) => provider.request(prompt, params),
+            ProviderBackend::Ollama(provider) => provider.request(prompt, params),
+            ProviderBackend::OpenRouter(provider) => provider.request(prompt, params),
+            ProviderBackend::Gemini(provider) => provider.request(prompt, params),
+            ProviderBackend::Mock(provider) => provider.request(prompt, params),
+        };
+        request.redacted()
+    }
+
+    /// Sends a tiny completion request to check the key, model and endpoint
+    pub async fn ping(&self) -> Result<Completion, ProviderError> {
+        self.complete(AIPrompt::build_ping_prompt(), &self.generation).await
+    }
+
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        let span = tracing::info_span!("completion", provider = self.name(), model = self.model());
+        let completion = self
+            .complete_with_backend(prompt, params)
+            .instrument(span)
+            .await?;
+
+        if let Some(usage) = completion.usage {
+            tracing::debug!(
+                prompt_tokens = usage.prompt_tokens,
+                completion_tokens = usage.completion_tokens,
+                "token usage"
+            );
+        }
+        Ok(completion)
+    }
+
+    async fn complete_with_backend(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        match &self.backend {
+            ProviderBackend::OpenAI(provider) => provider.complete(prompt, params).await,
+            ProviderBackend::Phind(provider) => provider.complete(prompt, params).await,
+            ProviderBackend::Groq(provider) => provider.complete(prompt, params).await,
+            ProviderBackend::Claude(provider) => provider.complete(prompt, params).await,
+            ProviderBackend::Ollama(provider) => provider.complete(prompt, params).await,
+            ProviderBackend::OpenRouter(provider) => provider.complete(prompt, params).await,
+            ProviderBackend::Gemini(provider) => provider.complete(prompt, params).await,
+            ProviderBackend::Mock(provider) => provider.complete(prompt, params).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_models_url() {
+        assert_eq!(
+            openai_models_url("https://api.openai.com/v1/chat/completions"),
+            "https://api.openai.com/v1/models"
+        );
+        assert_eq!(
+            openai_models_url("https://proxy.example.com/v1/"),
+            "https://proxy.example.com/v1/models"
+        );
+    }
+
+    #[test]
+    fn test_parse_model_list() {
+        let response = json!({
+            "data": [
+                { "id": "llama-3.1-8b-instant", "context_window": 131072 },
+                { "id": "gpt-4o-mini" }
+            ]
+        });
+
+        let models = parse_model_list(&response).unwrap();
+        assert_eq!(models[0].id, "llama-3.1-8b-instant");
+        assert_eq!(models[0].context_length, Some(131072));
+        assert_eq!(models[1].context_length, None);
+    }
+
+    #[test]
+    fn test_provider_request_redacted() {
+        let request = ProviderRequest::post("https://example.com", json!({ "model": "m" }))
+            .header("Authorization", "Bearer sk-secret")
+            .header("Content-Type", "application/json")
+            .query("key", "secret");
+
+        let redacted = request.redacted();
+        assert_eq!(redacted.headers["Authorization"], "********");
+        assert_eq!(redacted.headers["Content-Type"], "application/json");
+        assert_eq!(redacted.query["key"], "********");
+        assert_eq!(redacted.payload, request.payload);
+    }
+
+    #[test]
+    fn test_generation_params_native_fields() {
+        let params = GenerationParams {
+            temperature: Some(0.2),
+            max_tokens: Some(256),
+            top_p: None,
+            stop: Some(vec!["\n\n".to_string()]),
+        };
+
+        let mut payload = json!({ "model": "claude", "max_tokens": 4096 });
+        params.apply_to(
+            &mut payload,
+            ["temperature", "max_tokens", "top_p", "stop_sequences"],
+        );
+        assert_eq!(
+            payload,
+            json!({
+                "model": "claude",
+                "max_tokens": 256,
+                "temperature": 0.2,
+                "stop_sequences": ["\n\n"],
+            })
+        );
+
+        assert!(GenerationParams::default()
+            .native_fields(GenerationParams::KEYS)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_temperature_range_depends_on_provider() {
+        let params = GenerationParams {
+            temperature: Some(1.5),
+            ..GenerationParams::default()
+        };
+        assert!(params.validate(ProviderType::Openai).is_ok());
+        assert_eq!(
+            params.validate(ProviderType::Claude).unwrap_err(),
+            "temperature must be between 0 and 1 for claude, got 1.5"
+        );
+        assert!(GenerationParams {
+            temperature: Some(2.5),
+            ..GenerationParams::default()
         }
+        .validate(ProviderType::Gemini)
+        .is_err());
     }
 }
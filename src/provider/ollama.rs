@@ -1,4 +1,7 @@
-use super::{AIProvider, ProviderError};
+use super::{
+    get_json, AIProvider, Completion, GenerationParams, ModelInfo, ProviderError, ProviderRequest,
+    TokenUsage,
+};
 use crate::ai_prompt::AIPrompt;
 use async_trait::async_trait;
 use reqwest::StatusCode;
@@ -11,10 +14,11 @@ pub struct OllamaConfig {
 }
 
 impl OllamaConfig {
-    pub fn new(model: String) -> Self {
+    pub fn new(model: String, api_base_url: Option<String>) -> Self {
         Self {
             model,
-            api_base_url: "http://localhost:11434/api/generate".to_string(),
+            api_base_url: api_base_url
+                .unwrap_or_else(|| "http://localhost:11434/api/generate".to_string()),
         }
     }
 }
@@ -29,19 +33,12 @@ impl OllamaProvider {
         Self { client, config }
     }
 
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        let payload = json!({
-            "model": self.config.model,
-            "prompt": format!("{}\n\n{}", prompt.system_prompt, prompt.user_prompt),
-            "stream": false
-        });
-
-        let response = self
-            .client
-            .post(&self.config.api_base_url)
-            .json(&payload)
-            .send()
-            .await?;
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        let response = self.request(&prompt, params).send(&self.client).await?;
 
         let status = response.status();
 
@@ -54,7 +51,16 @@ impl OllamaProvider {
                     .and_then(|response| response.as_str())
                     .ok_or(ProviderError::NoCompletionChoice)?;
 
-                Ok(content.to_string())
+                let usage = response_json
+                    .get("prompt_eval_count")
+                    .and_then(Value::as_u64)
+                    .zip(response_json.get("eval_count").and_then(Value::as_u64))
+                    .map(|(prompt, completion)| TokenUsage::new(prompt, completion));
+
+                Ok(Completion {
+                    text: content.to_string(),
+                    usage,
+                })
             }
             _ => {
                 let error_text = response.text().await?;
@@ -69,7 +75,50 @@ impl OllamaProvider {
 
 #[async_trait]
 impl AIProvider for OllamaProvider {
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        self.complete(prompt).await
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        self.complete(prompt, params).await
+    }
+
+    fn request(&self, prompt: &AIPrompt, params: &GenerationParams) -> ProviderRequest {
+        let mut payload = json!({
+            "model": self.config.model,
+            "prompt": format!("{}\n\n{}", prompt.system_prompt, prompt.user_prompt),
+            "stream": false
+        });
+        let options = params.native_fields(["temperature", "num_predict", "top_p", "stop"]);
+        if !options.is_empty() {
+            payload["options"] = Value::Object(options);
+        }
+
+        ProviderRequest::post(&self.config.api_base_url, payload)
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let url = self
+            .config
+            .api_base_url
+            .replace("/api/generate", "/api/tags");
+        let response = get_json(self.client.get(url)).await?;
+        let models = response
+            .get("models")
+            .and_then(Value::as_array)
+            .ok_or(ProviderError::UnexpectedResponse)?;
+
+        Ok(models
+            .iter()
+            .filter_map(|model| model.get("name")?.as_str())
+            .map(|name| ModelInfo {
+                id: name.to_string(),
+                context_length: None,
+            })
+            .collect())
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
     }
 }
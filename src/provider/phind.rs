@@ -1,10 +1,7 @@
-use super::{AIProvider, ProviderError};
+use super::{AIProvider, Completion, GenerationParams, ModelInfo, ProviderError, ProviderRequest};
 use crate::ai_prompt::AIPrompt;
 use async_trait::async_trait;
-use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    StatusCode,
-};
+use reqwest::StatusCode;
 use serde_json::{json, Value};
 
 #[derive(Clone)]
@@ -14,10 +11,11 @@ pub struct PhindConfig {
 }
 
 impl PhindConfig {
-    pub fn new(model: Option<String>) -> Self {
+    pub fn new(model: Option<String>, api_base_url: Option<String>) -> Self {
         Self {
             model: model.unwrap_or_else(|| "Phind-70B".to_string()),
-            api_base_url: "https://https.extension.phind.com/agent/".to_string(),
+            api_base_url: api_base_url
+                .unwrap_or_else(|| "https://https.extension.phind.com/agent/".to_string()),
         }
     }
 }
@@ -32,15 +30,6 @@ impl PhindProvider {
         Self { client, config }
     }
 
-    fn create_headers() -> Result<HeaderMap, ProviderError> {
-        let mut headers = HeaderMap::new();
-        headers.insert("Content-Type", HeaderValue::from_static("application/json"));
-        headers.insert("User-Agent", HeaderValue::from_static(""));
-        headers.insert("Accept", HeaderValue::from_static("*/*"));
-        headers.insert("Accept-Encoding", HeaderValue::from_static("Identity"));
-        Ok(headers)
-    }
-
     fn parse_line(line: &str) -> Option<String> {
         let data = line.strip_prefix("data: ")?; // Extract data after "data: " prefix
         let json_value: Value = serde_json::from_str(data).ok()?;
@@ -62,27 +51,12 @@ impl PhindProvider {
             .collect()
     }
 
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        let payload = json!({
-            "additional_extension_context": "",
-            "allow_magic_buttons": true,
-            "is_vscode_extension": true,
-            "message_history": [{
-                "content": prompt.user_prompt,
-                "role": "user"
-            }],
-            "requested_model": self.config.model,
-            "user_input": prompt.user_prompt
-        });
-
-        let headers = Self::create_headers()?;
-        let response = self
-            .client
-            .post(&self.config.api_base_url)
-            .headers(headers)
-            .json(&payload)
-            .send()
-            .await?;
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        let response = self.request(&prompt, params).send(&self.client).await?;
 
         let status = response.status();
         match status {
@@ -93,7 +67,10 @@ impl PhindProvider {
                 if full_text.is_empty() {
                     return Err(ProviderError::NoCompletionChoice);
                 }
-                Ok(full_text)
+                Ok(Completion {
+                    text: full_text,
+                    usage: None,
+                })
             }
             _ => {
                 let error_text = response.text().await?;
@@ -114,7 +91,44 @@ impl PhindProvider {
 
 #[async_trait]
 impl AIProvider for PhindProvider {
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
-        self.complete(prompt).await
+    async fn complete(
+        &self,
+        prompt: AIPrompt,
+        params: &GenerationParams,
+    ) -> Result<Completion, ProviderError> {
+        self.complete(prompt, params).await
+    }
+
+    /// Phind does not accept generation parameters, so they are ignored
+    fn request(&self, prompt: &AIPrompt, _params: &GenerationParams) -> ProviderRequest {
+        let payload = json!({
+            "additional_extension_context": "",
+            "allow_magic_buttons": true,
+            "is_vscode_extension": true,
+            "message_history": [{
+                "content": prompt.user_prompt,
+                "role": "user"
+            }],
+            "requested_model": self.config.model,
+            "user_input": prompt.user_prompt
+        });
+
+        ProviderRequest::post(&self.config.api_base_url, payload)
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "")
+            .header("Accept", "*/*")
+            .header("Accept-Encoding", "Identity")
+    }
+
+    /// Phind has no listing endpoint, only the configured model is known
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        Ok(vec![ModelInfo {
+            id: self.config.model.clone(),
+            context_length: None,
+        }])
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
     }
 }
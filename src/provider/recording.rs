@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+
+use super::ProviderError;
+
+/// Env variable with a directory to save every provider HTTP exchange to
+pub const RECORD_ENV: &str = "LUMEN_RECORD_DIR";
+
+/// Env variable with a directory of recorded exchanges to answer requests from, offline
+pub const REPLAY_ENV: &str = "LUMEN_REPLAY_DIR";
+
+pub enum Mode {
+    Live,
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// Read once from `LUMEN_REPLAY_DIR` and `LUMEN_RECORD_DIR`, replaying wins if both are set
+pub fn mode() -> &'static Mode {
+    static MODE: OnceLock<Mode> = OnceLock::new();
+    MODE.get_or_init(|| {
+        let dir = |var| std::env::var_os(var).filter(|dir| !dir.is_empty());
+        match (dir(REPLAY_ENV), dir(RECORD_ENV)) {
+            (Some(dir), _) => Mode::Replay(dir.into()),
+            (None, Some(dir)) => Mode::Record(dir.into()),
+            (None, None) => Mode::Live,
+        }
+    })
+}
+
+/// A request and its response as saved on disk. Headers are left out, so API keys
+/// never end up in recordings.
+#[derive(Serialize, Deserialize)]
+struct Exchange {
+    method: String,
+    url: String,
+    request_body: Option<String>,
+    status: u16,
+    response_body: String,
+}
+
+/// Recordings are keyed by method, URL path and request body, so they replay against any host
+fn file_name(method: &Method, url: &Url, body: Option<&[u8]>) -> String {
+    // FNV-1a, stable across Rust versions unlike the std hasher
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let key = [
+        method.as_str().as_bytes(),
+        b" ",
+        url.path().as_bytes(),
+        b"\n",
+    ];
+    for byte in key.into_iter().chain(body).flatten() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    let path = url.path().trim_matches('/').replace(['/', ':'], "_");
+    format!("{}-{path}-{hash:016x}.json", method.as_str().to_lowercase())
+}
+
+pub fn save(
+    dir: &Path,
+    method: &Method,
+    url: &Url,
+    body: Option<&[u8]>,
+    status: u16,
+    response_body: &[u8],
+) -> Result<(), ProviderError> {
+    let exchange = Exchange {
+        method: method.to_string(),
+        url: url.to_string(),
+        request_body: body.map(|body| String::from_utf8_lossy(body).into_owned()),
+        status,
+        response_body: String::from_utf8_lossy(response_body).into_owned(),
+    };
+
+    let path = dir.join(file_name(method, url, body));
+    let recording_error = |e: &dyn std::fmt::Display| {
+        ProviderError::Recording(format!("could not save {}: {e}", path.display()))
+    };
+
+    fs::create_dir_all(dir).map_err(|e| recording_error(&e))?;
+    let json = serde_json::to_string_pretty(&exchange).map_err(|e| recording_error(&e))?;
+    fs::write(&path, json).map_err(|e| recording_error(&e))?;
+    tracing::debug!(path = %path.display(), "recorded exchange");
+    Ok(())
+}
+
+/// The recorded response to a request, as if it came from the network
+pub fn replay(
+    dir: &Path,
+    method: &Method,
+    url: &Url,
+    body: Option<&[u8]>,
+) -> Result<reqwest::Response, ProviderError> {
+    let path = dir.join(file_name(method, url, body));
+    let content = fs::read_to_string(&path).map_err(|e| {
+        ProviderError::Recording(format!(
+            "no recording of {method} {url} at {}: {e}",
+            path.display()
+        ))
+    })?;
+    let exchange: Exchange = serde_json::from_str(&content).map_err(|e| {
+        ProviderError::Recording(format!("invalid recording {}: {e}", path.display()))
+    })?;
+    tracing::debug!(path = %path.display(), "replayed exchange");
+
+    response(exchange.status, exchange.response_body.into_bytes())
+}
+
+pub fn response(
+    status: u16,
+    body: impl Into<reqwest::Body>,
+) -> Result<reqwest::Response, ProviderError> {
+    let response = http::Response::builder()
+        .status(status)
+        .body(body)
+        .map_err(|e| ProviderError::Recording(e.to_string()))?;
+    Ok(response.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_is_stable() {
+        let url = Url::parse("http://127.0.0.1:8080/v1/chat/completions").unwrap();
+        let name = file_name(&Method::POST, &url, Some(b"{}"));
+
+        assert!(name.starts_with("post-v1_chat_completions-"));
+        assert_eq!(name, file_name(&Method::POST, &url, Some(b"{}")));
+        assert_ne!(name, file_name(&Method::POST, &url, Some(b"{ }")));
+
+        let other_host = Url::parse("https://api.openai.com/v1/chat/completions").unwrap();
+        assert_eq!(name, file_name(&Method::POST, &other_host, Some(b"{}")));
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::LumenError;
+
+use super::Handler;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Start of the range left to servers, used for failures of lumen itself
+pub const LUMEN_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Absent for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        RpcError::new(INVALID_PARAMS, message)
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        RpcError::new(METHOD_NOT_FOUND, format!("unknown method `{method}`"))
+    }
+}
+
+/// Carries the same `kind` and exit code as `--format json` errors
+impl From<LumenError> for RpcError {
+    fn from(e: LumenError) -> Self {
+        RpcError {
+            code: LUMEN_ERROR,
+            message: e.to_string(),
+            data: Some(json!({ "kind": e.kind(), "exit_code": e.exit_code() })),
+        }
+    }
+}
+
+/// Deserializes a method's params, a request without params counts as `{}`
+pub fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(|e| RpcError::invalid_params(e.to_string()))
+}
+
+pub fn to_result(value: impl Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| LumenError::from(e).into())
+}
+
+/// Handles one JSON-RPC message, `None` for notifications
+pub async fn respond(handler: &dyn Handler, message: &str) -> Option<String> {
+    let response = match serde_json::from_str::<Value>(message) {
+        Err(e) => error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+        Ok(value) => {
+            let id = value.get("id").cloned().unwrap_or(Value::Null);
+            match serde_json::from_value::<Request>(value) {
+                Ok(request) if request.jsonrpc == "2.0" => {
+                    tracing::debug!(method = %request.method, "request");
+                    let result = handler.handle(&request.method, request.params).await;
+                    let id = request.id?;
+                    match result {
+                        Ok(result) => Response {
+                            jsonrpc: "2.0",
+                            id,
+                            result: Some(result),
+                            error: None,
+                        },
+                        Err(error) => error_response(id, error),
+                    }
+                }
+                Ok(_) => error_response(
+                    id,
+                    RpcError::new(INVALID_REQUEST, "`jsonrpc` must be \"2.0\""),
+                ),
+                Err(e) => error_response(id, RpcError::new(INVALID_REQUEST, e.to_string())),
+            }
+        }
+    };
+
+    serde_json::to_string(&response).ok()
+}
+
+fn error_response(id: Value, error: RpcError) -> Response {
+    Response {
+        jsonrpc: "2.0",
+        id,
+        result: None,
+        error: Some(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    struct Echo;
+
+    #[async_trait]
+    impl Handler for Echo {
+        async fn handle(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+            match method {
+                "echo" => Ok(params),
+                _ => Err(RpcError::method_not_found(method)),
+            }
+        }
+    }
+
+    async fn call(message: &str) -> Option<Value> {
+        respond(&Echo, message)
+            .await
+            .map(|response| serde_json::from_str(&response).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_respond() {
+        let response = call(r#"{"jsonrpc":"2.0","id":1,"method":"echo","params":{"a":1}}"#).await;
+        assert_eq!(
+            response,
+            Some(json!({ "jsonrpc": "2.0", "id": 1, "result": { "a": 1 } }))
+        );
+
+        let response = call(r#"{"jsonrpc":"2.0","id":"x","method":"nope"}"#).await;
+        assert_eq!(response.unwrap()["error"]["code"], METHOD_NOT_FOUND);
+
+        let response = call("{not json").await.unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+
+        let response = call(r#"{"jsonrpc":"1.0","id":2,"method":"echo"}"#).await;
+        assert_eq!(response.unwrap()["error"]["code"], INVALID_REQUEST);
+
+        // Notifications are handled but not answered
+        assert_eq!(call(r#"{"jsonrpc":"2.0","method":"echo"}"#).await, None);
+    }
+}
@@ -0,0 +1,192 @@
+//! JSON-RPC 2.0 over stdio, one message per line, or over local HTTP, one message per POST
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::error::LumenError;
+
+pub mod jsonrpc;
+
+pub use jsonrpc::{respond, RpcError};
+
+/// Requests larger than this are refused, diffs are sent by lumen, not to it
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+#[async_trait]
+pub trait Handler: Send + Sync {
+    /// Result of `method`, `params` is `Null` when the request had none
+    async fn handle(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, RpcError>;
+}
+
+/// Runs `task` on the blocking thread pool, eg: git commands, which would otherwise stall
+/// the other requests
+pub async fn blocking<T, F>(task: F) -> Result<T, RpcError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, RpcError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| LumenError::CommandError(e.to_string()))?
+}
+
+/// Answers requests read from stdin until it is closed. Requests run concurrently, so
+/// responses may come back in a different order, matched by their `id`.
+pub async fn serve_stdio(handler: Arc<dyn Handler>) -> Result<(), LumenError> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(response) = receiver.recv().await {
+            stdout.write_all(response.as_bytes()).await?;
+            stdout.write_all(b"\n").await?;
+            stdout.flush().await?;
+        }
+        Ok::<_, io::Error>(())
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let handler = Arc::clone(&handler);
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            if let Some(response) = respond(handler.as_ref(), &line).await {
+                let _ = sender.send(response);
+            }
+        });
+    }
+
+    // The writer stops once the requests still running have answered
+    drop(sender);
+    writer.await.map_err(io::Error::other)??;
+    Ok(())
+}
+
+/// Answers `POST` requests on `addr` until interrupted
+pub async fn serve_http(handler: Arc<dyn Handler>, addr: &str) -> Result<(), LumenError> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    if !local_addr.ip().is_loopback() {
+        tracing::warn!(
+            "{local_addr} is reachable from other machines, which could then use your API key"
+        );
+    }
+    eprintln!("Listening on http://{local_addr}");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let handler = Arc::clone(&handler);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(handler.as_ref(), stream, local_addr).await {
+                tracing::debug!(%peer, "connection failed: {e}");
+            }
+        });
+    }
+}
+
+/// Whether `host`, a `Host` header, names the server by a loopback address or its own
+/// address. Any other name may be a DNS rebinding attack, a web page whose domain now
+/// resolves to 127.0.0.1.
+fn allowed_host(host: &str, local_addr: SocketAddr) -> bool {
+    let port = local_addr.port();
+    ["127.0.0.1", "localhost", "[::1]"]
+        .iter()
+        .map(|name| format!("{name}:{port}"))
+        .chain([local_addr.to_string()])
+        .any(|allowed| host.eq_ignore_ascii_case(&allowed))
+}
+
+async fn handle_connection(
+    handler: &dyn Handler,
+    stream: TcpStream,
+    local_addr: SocketAddr,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let method = request_line
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let mut length = 0;
+    let mut json = false;
+    let mut host = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        let (name, value) = (name.trim().to_lowercase(), value.trim());
+        if name == "content-length" {
+            length = value.parse().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid content-length")
+            })?;
+        } else if name == "content-type" {
+            json = value.starts_with("application/json");
+        } else if name == "host" {
+            host = Some(value.to_string());
+        }
+    }
+
+    // Browsers only send `application/json` cross-origin after a preflight, which is never
+    // answered. A page can still send it same-origin after rebinding its domain to
+    // 127.0.0.1, its domain is then in `Host`.
+    let (status, body) = if !host.is_some_and(|host| allowed_host(&host, local_addr)) {
+        ("403 Forbidden", String::new())
+    } else if method != "POST" {
+        ("405 Method Not Allowed", String::new())
+    } else if !json {
+        ("415 Unsupported Media Type", String::new())
+    } else if length > MAX_BODY_BYTES {
+        ("413 Payload Too Large", String::new())
+    } else {
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await?;
+        match respond(handler, &String::from_utf8_lossy(&body)).await {
+            Some(response) => ("200 OK", response),
+            None => ("204 No Content", String::new()),
+        }
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_host() {
+        let local_addr: SocketAddr = "127.0.0.1:7878".parse().unwrap();
+        assert!(allowed_host("127.0.0.1:7878", local_addr));
+        assert!(allowed_host("localhost:7878", local_addr));
+        assert!(allowed_host("[::1]:7878", local_addr));
+        assert!(!allowed_host("attacker.example:7878", local_addr));
+        assert!(!allowed_host("localhost:80", local_addr));
+        assert!(!allowed_host("127.0.0.1", local_addr));
+    }
+}
@@ -0,0 +1,86 @@
+//! Fixtures shared by the unit tests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{
+    config::cli::ProviderType,
+    provider::{GenerationParams, LumenProvider},
+    Repo,
+};
+
+/// The mock provider, answering without any network access
+pub fn mock_provider() -> LumenProvider {
+    LumenProvider::new(
+        reqwest::Client::new(),
+        ProviderType::Mock,
+        None,
+        None,
+        None,
+        GenerationParams::default(),
+    )
+    .unwrap()
+}
+
+/// A throwaway repository whose `HEAD` changes `README.md` on top of a root commit, removed
+/// on drop
+pub struct TempRepo {
+    dir: PathBuf,
+}
+
+impl TempRepo {
+    pub fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "lumen-unit-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let temp = TempRepo { dir };
+        temp.git(&["init", "-q"]);
+        temp.git(&["config", "user.name", "Test"]);
+        temp.git(&["config", "user.email", "test@example.com"]);
+        temp.git(&["config", "commit.gpgsign", "false"]);
+        temp.write("README.md", "# Hello\n");
+        temp.git(&["add", "README.md"]);
+        temp.git(&["commit", "-q", "-m", "Add README"]);
+        temp.write("README.md", "# Hello\n\nA test repository\n");
+        temp.git(&["commit", "-q", "-am", "Describe the repository"]);
+        temp
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn repo(&self) -> Repo {
+        Repo::discover(Some(&self.dir)).unwrap()
+    }
+
+    pub fn git(&self, args: &[&str]) {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&self.dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+    }
+
+    /// Writes `file`, creating its parent directories
+    pub fn write(&self, file: &str, content: &str) {
+        let path = self.dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
+
+impl Drop for TempRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
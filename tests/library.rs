@@ -0,0 +1,61 @@
+//! Uses lumen as a library, the way tools embedding it do
+
+use lumen::command::Command;
+use lumen::{
+    AIPrompt, CommandOutput, Diff, ExplainCommand, GenerationParams, GitEntity, LumenProvider,
+    ProviderType,
+};
+
+fn mock_provider() -> LumenProvider {
+    LumenProvider::new(
+        reqwest::Client::new(),
+        ProviderType::Mock,
+        None,
+        None,
+        None,
+        GenerationParams::default(),
+    )
+    .unwrap()
+}
+
+fn explain_command() -> ExplainCommand {
+    ExplainCommand {
+        git_entity: GitEntity::Diff(Diff::WorkingTree {
+            staged: true,
+            diff: "+++ b/README.md\n+# Hello\n".to_string(),
+        }),
+        query: Some("What changed?".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn test_explain_returns_report() {
+    let report = explain_command().generate(&mock_provider()).await.unwrap();
+
+    assert_eq!(report.text, "Mock response from mock");
+    assert_eq!(report.query.as_deref(), Some("What changed?"));
+    assert!(report.usage.is_some());
+}
+
+#[tokio::test]
+async fn test_execute_returns_explanation() {
+    let output = explain_command().execute(&mock_provider()).await.unwrap();
+
+    let CommandOutput::Explanation { git_entity, report } = output else {
+        panic!("expected an explanation, got {output:?}");
+    };
+    assert!(matches!(
+        git_entity,
+        GitEntity::Diff(Diff::WorkingTree { staged: true, .. })
+    ));
+    assert_eq!(report.text, "Mock response from mock");
+}
+
+#[test]
+fn test_prompt_preview() {
+    let prompt = AIPrompt::build_explain_prompt(&explain_command()).unwrap();
+    assert!(prompt.user_prompt.contains("+# Hello"));
+
+    let request = mock_provider().preview(&prompt);
+    assert_eq!(request.payload["model"], "mock");
+}
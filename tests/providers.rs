@@ -0,0 +1,518 @@
+//! Runs lumen against a local stand-in for each provider's API, no network or tokens needed
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone)]
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+/// Answers like the real APIs, routed by path, and keeps every request it received
+struct StandIn {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl StandIn {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let received = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Some(request) = handle(stream) {
+                    received.lock().unwrap().push(request);
+                }
+            }
+        });
+
+        StandIn { addr, requests }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.addr)
+    }
+
+    fn last_request(&self) -> Request {
+        self.requests
+            .lock()
+            .unwrap()
+            .last()
+            .cloned()
+            .expect("no request received")
+    }
+}
+
+fn handle(mut stream: TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream.try_clone().ok()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let path = target.split('?').next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+    // The query is where Gemini takes the API key
+    if let Some((_, query)) = target.split_once('?') {
+        headers.push(("query".to_string(), query.to_string()));
+    }
+
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    let request = Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    };
+
+    let body = respond(&request);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).ok()?;
+    Some(request)
+}
+
+fn respond(request: &Request) -> String {
+    let path = request.path.as_str();
+    let response = match (request.method.as_str(), path) {
+        ("POST", path) if path.ends_with("/chat/completions") => {
+            let provider = path
+                .trim_start_matches('/')
+                .split('/')
+                .next()
+                .unwrap_or_default();
+            json!({
+                "choices": [{ "message": { "content": format!("{provider} draft") } }],
+                "usage": { "prompt_tokens": 10, "completion_tokens": 2 }
+            })
+        }
+        ("POST", "/claude/v1/messages") => json!({
+            "content": [{ "text": "claude draft" }],
+            "usage": { "input_tokens": 10, "output_tokens": 2 }
+        }),
+        ("POST", "/ollama/api/generate") => json!({ "response": "ollama draft" }),
+        ("POST", "/gemini/models/m:generateContent") => json!({
+            "candidates": [{ "content": { "parts": [{ "text": "gemini draft" }] } }]
+        }),
+        ("POST", "/phind/agent/") => {
+            let chunk = json!({ "choices": [{ "delta": { "content": "phind draft" } }] });
+            return format!("data: {chunk}\n\ndata: [DONE]\n");
+        }
+        ("GET", "/ollama/api/tags") => json!({ "models": [{ "name": "m" }] }),
+        ("GET", "/gemini/models") => json!({
+            "models": [{
+                "name": "models/m",
+                "inputTokenLimit": 1000,
+                "supportedGenerationMethods": ["generateContent"]
+            }]
+        }),
+        ("GET", path) if path.ends_with("/models") => {
+            json!({ "data": [{ "id": "m", "context_window": 1000 }] })
+        }
+        _ => json!({ "error": { "message": format!("no route for {path}") } }),
+    };
+    response.to_string()
+}
+
+/// A repository with one staged file, and an empty config directory
+struct Sandbox {
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("lumen-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("repo")).unwrap();
+        fs::create_dir_all(dir.join("config")).unwrap();
+
+        let sandbox = Sandbox { dir };
+        sandbox.git(&["init", "-q"]);
+        sandbox.git(&["config", "user.name", "Test"]);
+        sandbox.git(&["config", "user.email", "test@example.com"]);
+        sandbox.write("README.md", "# Hello\n");
+        sandbox.git(&["add", "README.md"]);
+        sandbox
+    }
+
+    fn git(&self, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(self.dir.join("repo"))
+            .output()
+            .unwrap();
+        assert!(status.status.success(), "git {args:?} failed");
+    }
+
+    fn write(&self, file: &str, content: &str) {
+        fs::write(self.dir.join("repo").join(file), content).unwrap();
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    fn lumen(&self, args: &[&str], env: &[(&str, &Path)]) -> Output {
+        self.lumen_in(&self.dir.join("repo"), args, env)
+    }
+
+    fn lumen_in(&self, dir: &Path, args: &[&str], env: &[(&str, &Path)]) -> Output {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_lumen"));
+        for (name, _) in std::env::vars() {
+            if name.starts_with("LUMEN_") {
+                command.env_remove(name);
+            }
+        }
+        command
+            .args(args)
+            .current_dir(dir)
+            .env("HOME", &self.dir)
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .envs(env.iter().copied())
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "lumen failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Provider, endpoint on the stand-in, and where its native payload puts `max_tokens`
+const PROVIDERS: [(&str, &str, &str); 7] = [
+    ("openai", "/openai/chat/completions", "/max_tokens"),
+    ("groq", "/groq/chat/completions", "/max_tokens"),
+    ("openrouter", "/openrouter/chat/completions", "/max_tokens"),
+    ("claude", "/claude/v1/messages", "/max_tokens"),
+    ("ollama", "/ollama/api/generate", "/options/num_predict"),
+    (
+        "gemini",
+        "/gemini/models/{model}:generateContent",
+        "/generationConfig/maxOutputTokens",
+    ),
+    ("phind", "/phind/agent/", ""),
+];
+
+#[test]
+fn test_draft_with_each_provider() {
+    let server = StandIn::start();
+    let sandbox = Sandbox::new("draft");
+
+    for (provider, path, max_tokens) in PROVIDERS {
+        let api_base = server.url(path);
+        let args = [
+            "-p",
+            provider,
+            "-k",
+            "test-key",
+            "-m",
+            "m",
+            "--api-base",
+            &api_base,
+            "--max-tokens",
+            "64",
+            "draft",
+        ];
+        let output = sandbox.lumen(&args, &[]);
+        assert_eq!(stdout(&output), format!("{provider} draft"));
+
+        let request = server.last_request();
+        let body = request.json();
+        assert!(
+            request.body.contains("README.md"),
+            "{provider}: diff not sent"
+        );
+        if !max_tokens.is_empty() {
+            assert_eq!(body.pointer(max_tokens), Some(&json!(64)), "{provider}");
+        }
+
+        let key_sent = match provider {
+            "claude" => request.header("x-api-key") == Some("test-key"),
+            "gemini" => request.header("query") == Some("key=test-key"),
+            "ollama" | "phind" => true,
+            _ => request.header("authorization") == Some("Bearer test-key"),
+        };
+        assert!(key_sent, "{provider}: API key not sent");
+    }
+}
+
+#[test]
+fn test_models_with_each_provider() {
+    let server = StandIn::start();
+    let sandbox = Sandbox::new("models");
+
+    for (provider, path, _) in PROVIDERS {
+        let api_base = server.url(path);
+        let args = [
+            "-p",
+            provider,
+            "-k",
+            "test-key",
+            "-m",
+            "m",
+            "--api-base",
+            &api_base,
+            "--format",
+            "json",
+            "models",
+        ];
+        let output = sandbox.lumen(&args, &[]);
+        let document: Value = serde_json::from_str(&stdout(&output)).unwrap();
+
+        assert_eq!(document["provider"], provider);
+        assert_eq!(document["models"][0]["id"], "m", "{provider}");
+        assert_eq!(document["models"][0]["current"], true, "{provider}");
+    }
+}
+
+#[test]
+fn test_mock_provider_fixtures() {
+    let sandbox = Sandbox::new("mock");
+    let fixtures = sandbox.path("fixtures.toml");
+    fs::write(
+        &fixtures,
+        indoc::indoc! {r#"
+            models = ["mock-small", "mock-large"]
+
+            [[responses]]
+            contains = "README.md"
+            response = "docs: add readme ({{model}})"
+        "#},
+    )
+    .unwrap();
+
+    let output = sandbox.lumen(
+        &["-p", "mock", "-m", "mock-small", "draft"],
+        &[("LUMEN_MOCK_FIXTURES", &fixtures)],
+    );
+    assert_eq!(stdout(&output), "docs: add readme (mock-small)");
+
+    let output = sandbox.lumen(&["-p", "mock", "draft"], &[]);
+    assert_eq!(stdout(&output), "Mock response from mock");
+}
+
+#[test]
+fn test_record_and_replay() {
+    let server = StandIn::start();
+    let sandbox = Sandbox::new("replay");
+    let recordings = sandbox.path("recordings");
+
+    let api_base = server.url("/openai/chat/completions");
+    let args = [
+        "-p",
+        "openai",
+        "-k",
+        "test-key",
+        "--api-base",
+        &api_base,
+        "draft",
+    ];
+    let recorded = stdout(&sandbox.lumen(&args, &[("LUMEN_RECORD_DIR", &recordings)]));
+    assert_eq!(recorded, "openai draft");
+
+    let files: Vec<_> = fs::read_dir(&recordings).unwrap().flatten().collect();
+    assert_eq!(files.len(), 1);
+    let recording = fs::read_to_string(files[0].path()).unwrap();
+    assert!(!recording.contains("test-key"), "API key recorded");
+
+    // Nothing listens on the discard port, the answer has to come from the recording
+    let args = [
+        "-p",
+        "openai",
+        "-k",
+        "other-key",
+        "--api-base",
+        "http://127.0.0.1:9/openai/chat/completions",
+        "draft",
+    ];
+    let replayed = stdout(&sandbox.lumen(&args, &[("LUMEN_REPLAY_DIR", &recordings)]));
+    assert_eq!(replayed, recorded);
+}
+
+#[test]
+fn test_explain_merges_tags_and_stashes() {
+    let sandbox = Sandbox::new("entities");
+    sandbox.git(&["commit", "-q", "-m", "docs: add readme"]);
+    sandbox.git(&["tag", "v0.1"]);
+    sandbox.git(&["checkout", "-q", "-b", "feature"]);
+    sandbox.write("feature.txt", "feature\n");
+    sandbox.git(&["add", "feature.txt"]);
+    sandbox.git(&["commit", "-q", "-m", "feat: add feature"]);
+    sandbox.git(&["checkout", "-q", "-"]);
+    sandbox.write("main.txt", "main\n");
+    sandbox.git(&["add", "main.txt"]);
+    sandbox.git(&["commit", "-q", "-m", "fix: main"]);
+    sandbox.git(&["merge", "-q", "--no-edit", "feature"]);
+    sandbox.git(&["tag", "-a", "v0.2", "-m", "Release 0.2"]);
+    sandbox.write("README.md", "# Hello again\n");
+    sandbox.git(&["stash", "-q"]);
+
+    let entity = |reference: &str| {
+        let output = sandbox.lumen(
+            &["-p", "mock", "--format", "json", "explain", reference],
+            &[],
+        );
+        let document: Value = serde_json::from_str(&stdout(&output)).unwrap();
+        document["results"][0]["entity"].clone()
+    };
+
+    let merge = entity("HEAD");
+    assert_eq!(merge["type"], "commit");
+    assert_eq!(merge["parents"].as_array().unwrap().len(), 2);
+    assert_eq!(merge["merge_diff"], "first-parent");
+
+    let tag = entity("v0.2");
+    assert_eq!(tag["type"], "tag");
+    assert_eq!(tag["previous"], "v0.1");
+    assert_eq!(tag["message"], "Release 0.2");
+
+    let stash = entity("stash@{0}");
+    assert_eq!(stash["type"], "stash");
+}
+
+#[test]
+fn test_explain_all_changes() {
+    let sandbox = Sandbox::new("all");
+    sandbox.write("notes.txt", "untracked notes\n");
+
+    let args = [
+        "-p",
+        "mock",
+        "--format",
+        "json",
+        "--dry-run",
+        "explain",
+        "--diff",
+        "--all",
+        "--untracked",
+    ];
+    let document: Value = serde_json::from_str(&stdout(&sandbox.lumen(&args, &[]))).unwrap();
+    let prompt = &document["prompts"][0];
+    assert_eq!(prompt["entity"]["type"], "working_tree_all");
+
+    let user_prompt = prompt["user_prompt"].as_str().unwrap();
+    let staged = user_prompt.find("Staged, part of the next commit").unwrap();
+    let untracked = user_prompt.find("Untracked, new files").unwrap();
+    assert!(user_prompt[staged..untracked].contains("+# Hello"));
+    assert!(user_prompt[untracked..].contains("+untracked notes"));
+}
+
+#[test]
+fn test_run_against_another_repository() {
+    let sandbox = Sandbox::new("other-repo");
+    let repo = sandbox.path("repo");
+    let repo = repo.to_str().unwrap();
+
+    let args = [
+        "-C",
+        repo,
+        "-p",
+        "mock",
+        "--format",
+        "json",
+        "--dry-run",
+        "draft",
+    ];
+    let output = sandbox.lumen_in(&sandbox.path("config"), &args, &[]);
+    let document: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let user_prompt = document["prompts"][0]["user_prompt"].as_str().unwrap();
+    assert!(user_prompt.contains("+# Hello"));
+
+    let output = sandbox.lumen_in(&sandbox.path("config"), &["-p", "mock", "draft"], &[]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not inside a git repository"));
+}
+
+#[test]
+fn test_explain_binary_and_latin1_files() {
+    let sandbox = Sandbox::new("binary");
+    let repo = sandbox.path("repo");
+    fs::write(repo.join("logo.png"), [0x89, b'P', b'N', b'G', 0, 1, 2, 3]).unwrap();
+    sandbox.git(&["add", "logo.png"]);
+    sandbox.git(&["commit", "-q", "-m", "Add logo"]);
+
+    fs::write(
+        repo.join("logo.png"),
+        [0x89, b'P', b'N', b'G', 0, 1, 2, 3, 4, 5],
+    )
+    .unwrap();
+    // "café" in Latin-1
+    fs::write(repo.join("menu.txt"), [b'c', b'a', b'f', 0xe9, b'\n']).unwrap();
+    sandbox.git(&["add", "logo.png", "menu.txt"]);
+    sandbox.git(&["commit", "-q", "-m", "Update logo and menu"]);
+
+    let args = [
+        "-p",
+        "mock",
+        "--format",
+        "json",
+        "--dry-run",
+        "explain",
+        "HEAD",
+    ];
+    let output = sandbox.lumen(&args, &[]);
+    let document: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let user_prompt = document["prompts"][0]["user_prompt"].as_str().unwrap();
+    assert!(user_prompt.contains("Binary file logo.png (image) changed, 8 B -> 10 B (+2 B)"));
+    assert!(user_prompt.contains("+caf\u{fffd}"));
+    assert!(!user_prompt.contains("GIT binary patch"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid UTF-8"));
+}